@@ -1,6 +1,7 @@
 use version_check as rustc;
 
 fn main() {
+    println!(r#"cargo:rustc-check-cfg=cfg(has_std_once_cell, values("true", "false"))"#);
     if rustc::is_min_version("1.70.0").unwrap_or(false) {
         println!(r#"cargo:rustc-cfg=has_std_once_cell="true""#)
     } else {
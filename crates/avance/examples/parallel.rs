@@ -0,0 +1,16 @@
+use std::thread;
+use std::time::Duration;
+
+use avance::*;
+use rayon::prelude::*;
+
+fn main() {
+    (0..1000)
+        .into_par_iter()
+        .avance()
+        .with_style(Style::Balloon)
+        .with_desc("rayon")
+        .for_each(|_| {
+            thread::sleep(Duration::from_millis(3));
+        });
+}
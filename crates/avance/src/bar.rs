@@ -1,44 +1,98 @@
 //! A progress bar and all utilities.
 
 use crossterm::cursor::{MoveToColumn, MoveUp};
-use crossterm::style::Print;
-use crossterm::terminal::{self, Clear, ClearType};
-use crossterm::tty::IsTty;
-use crossterm::QueueableCommand;
+use crossterm::style::Stylize;
+use crossterm::terminal::{Clear, ClearType};
+use crossterm::{Command, QueueableCommand};
 use std::borrow::Cow;
 use std::cmp::{max, min};
-use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
-use std::io::{stderr, Result, Write};
+use std::fmt::{Display, Formatter, Write as _};
+use std::io::{stdout, Result, Write};
 use std::sync::{
-    atomic::{AtomicU16, AtomicU64, Ordering},
+    atomic::{AtomicI64, AtomicU64, Ordering},
     Arc, Mutex,
 };
-use std::time::Instant;
-
-#[cfg(has_std_once_cell = "false")]
-use once_cell::sync::OnceCell as OnceLock;
-#[cfg(has_std_once_cell = "true")]
-use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{iter, mem};
 
 use super::*;
+use counter::ShardedCounter;
+use estimator::{RateEstimator, Smoothed};
+use format::{display_width, OrUnknown, Scaled, Time};
+use group::{next_id, AtomicState, Group, Pos, ID};
+use hook::Hooks;
+use style::{no_color, Color, Colors, Status};
+use target::terminal_size;
+use template::BarTemplate;
 
 /// The progress bar
 #[derive(Debug, Clone)]
 pub struct AvanceBar {
     state: AtomicState,
     progress: Arc<AtomicProgress>,
+    parent: Option<Arc<Parent>>,
+}
+
+/// How a parent progress bar advances along with its children, see [`AvanceBar::child`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Aggregate {
+    /// The parent advances independently of its children
+    #[default]
+    None,
+
+    /// The parent advances along with its children, and its total length
+    /// grows by the total length of each child.
+    Sum,
+
+    /// The parent advances by one whenever a child completes
+    Count,
+}
+
+/// The numbers shown by a progress bar at some moment, see [`AvanceBar::snapshot`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProgressSnapshot {
+    /// Number of finished iterations
+    pub n: u64,
+    /// Total length, or None if unknown
+    pub total: Option<u64>,
+    /// Time since the progress bar started
+    pub elapsed: Duration,
+    /// Iterations per second, or None if unknown
+    pub rate: Option<f64>,
+    /// Estimated remaining time, or None if unknown
+    pub eta: Option<Duration>,
+    /// Finished percentage from 0 to 100, or None if the total length is unknown
+    pub percent: Option<f64>,
+}
+
+impl From<Stats> for ProgressSnapshot {
+    fn from(stats: Stats) -> Self {
+        let rate = Some(stats.its).filter(|its| its.is_finite());
+        let eta = match (stats.total, rate) {
+            (Some(total), Some(rate)) if stats.n > 0 && rate > 0.0 => Some(
+                Duration::from_secs_f64(total.saturating_sub(stats.n) as f64 / rate),
+            ),
+            _ => None,
+        };
+
+        Self {
+            n: stats.n,
+            total: stats.total,
+            elapsed: Duration::from_secs_f64(stats.elapsed),
+            rate,
+            eta,
+            percent: stats.pct().map(|pct| pct * 100.0),
+        }
+    }
 }
 
 // Public Interface
 impl AvanceBar {
     /// Create a new progress bar
     pub fn new(total: u64) -> Self {
-        let progress = Arc::new(AtomicProgress::new());
-        let pb = AvanceBar {
-            state: Arc::new(Mutex::new(State::new(Some(total), Arc::clone(&progress)))),
-            progress,
-        };
+        let pb = Self::with_total(Some(total));
         pb.refresh();
         pb
     }
@@ -85,6 +139,41 @@ impl AvanceBar {
         }
     }
 
+    /// Wrap a reader, which advances the progress bar by the bytes read.
+    ///
+    /// Seeking the reader moves the progress bar to the new offset.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use avance::{AvanceBar, UnitScale};
+    /// use std::fs::File;
+    /// use std::io::{copy, sink};
+    ///
+    /// let file = File::open("data.bin").unwrap();
+    /// let pb = AvanceBar::new(file.metadata().unwrap().len()).with_bytes(UnitScale::IEC);
+    /// copy(&mut pb.wrap_read(file), &mut sink()).unwrap();
+    /// ```
+    pub fn wrap_read<R>(&self, reader: R) -> AvanceRead<R> {
+        AvanceRead::new(reader, self.clone())
+    }
+
+    /// Wrap a writer, which advances the progress bar by the bytes written.
+    ///
+    /// Seeking the writer moves the progress bar to the new offset.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{AvanceBar, UnitScale};
+    /// use std::io::Write;
+    ///
+    /// let pb = AvanceBar::new(1024).with_bytes(UnitScale::IEC);
+    /// let mut writer = pb.wrap_write(Vec::new());
+    /// writer.write_all(&[0; 1024]).unwrap();
+    /// ```
+    pub fn wrap_write<W>(&self, writer: W) -> AvanceWrite<W> {
+        AvanceWrite::new(writer, self.clone())
+    }
+
     /// Builder-like function for a progress bar with a given style
     /// (default: [`Style::ASCII`]).
     ///
@@ -119,6 +208,66 @@ impl AvanceBar {
         self
     }
 
+    /// Builder-like function for a progress bar with a spinner, which is shown
+    /// when the total length is unknown (default: no spinner).
+    ///
+    /// See available spinners in [`Spinner`]
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{AvanceBar, Spinner};
+    /// let pb = AvanceBar::new(100).with_spinner(Spinner::Dots);
+    /// ```
+    pub fn with_spinner(self, spinner: Spinner) -> Self {
+        self.set_spinner(spinner);
+        self
+    }
+
+    /// Builder-like function for a progress bar showing a bouncing animation
+    /// when the total length is unknown (default: false).
+    ///
+    /// The animation uses the first and the last character of the style.
+    pub fn with_bounce(self, bounce: bool) -> Self {
+        self.set_bounce(bounce);
+        self
+    }
+
+    /// Builder-like function for a colored progress bar (default: no colors).
+    ///
+    /// Colors are dropped automatically on non-terminal targets, or if the
+    /// `NO_COLOR` environment variable is set.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{AvanceBar, Color, Colors};
+    /// let pb = AvanceBar::new(1000).with_colors(
+    ///     Colors::new()
+    ///         .with_filled(Color::Cyan)
+    ///         .with_desc(Color::Yellow)
+    ///         .with_done(Color::Green),
+    /// );
+    /// ```
+    pub fn with_colors(self, colors: Colors) -> Self {
+        self.set_colors(colors);
+        self
+    }
+
+    /// Builder-like function for a progress bar with a rate estimator,
+    /// which also decides the ETA.
+    ///
+    /// See available estimators in [`estimator`](crate::estimator)
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{AvanceBar, estimator::SlidingWindow};
+    /// # use std::time::Duration;
+    /// let pb = AvanceBar::new(1000).with_estimator(SlidingWindow::new(Duration::from_secs(5)));
+    /// ```
+    pub fn with_estimator(self, estimator: impl RateEstimator + 'static) -> Self {
+        self.set_estimator(estimator);
+        self
+    }
+
     /// Builder-like function for a progress bar with width
     ///
     /// If width is larger than terminal width, progress bar will adjust
@@ -148,13 +297,69 @@ impl AvanceBar {
 
     /// Builder-like function for displaying human readable numbers in a progress bar.
     ///
-    /// If unit_scale (default: false) is set true, prints the number of iterations
-    /// with an appropriate SI metric prefix (k = 10^3, M = 10^6, etc.)
-    pub fn with_unit_scale(self, unit_scale: bool) -> Self {
+    /// If unit_scale (default: [`UnitScale::None`]) is set, prints the number of iterations
+    /// and the rate with an appropriate prefix. `true` stands for SI metric prefixes
+    /// (k = 10^3, M = 10^6, etc.)
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{AvanceBar, UnitScale};
+    /// let pb = AvanceBar::new(1_000_000).with_unit_scale(true);
+    /// let pb = AvanceBar::new(1_000_000).with_unit_scale(UnitScale::IEC);
+    /// ```
+    pub fn with_unit_scale(self, unit_scale: impl Into<UnitScale>) -> Self {
         self.set_unit_scale(unit_scale);
         self
     }
 
+    /// Builder-like function for a progress bar with a unit name (default: `it`).
+    ///
+    /// The rate is displayed as `{unit}/s`, or `s/{unit}` if it's slower
+    /// than one unit per second.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000).with_unit("file");
+    /// ```
+    pub fn with_unit(self, unit: impl Into<Cow<'static, str>>) -> Self {
+        self.set_unit(unit);
+        self
+    }
+
+    /// Builder-like function for a progress bar counting bytes, which displays
+    /// like `12.3MiB/1.00GiB [00:05<01:20, 2.40MiB/s]`.
+    ///
+    /// `unit_scale` chooses between SI (kB = 1000 bytes) and IEC (KiB = 1024 bytes) prefixes.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{AvanceBar, UnitScale};
+    /// let pb = AvanceBar::new(1 << 30).with_bytes(UnitScale::IEC);
+    /// pb.update(4096);
+    /// ```
+    pub fn with_bytes(self, unit_scale: UnitScale) -> Self {
+        self.set_bytes(unit_scale);
+        self
+    }
+
+    /// Builder-like function for a progress bar with a user-defined template.
+    ///
+    /// A template decides the layout of a progress bar, see [`template`](crate::template)
+    /// for available placeholders. An error is returned if the template is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000)
+    ///     .with_template("{desc} {bar:40} {pos}/{len} {rate} eta {eta}")
+    ///     .unwrap();
+    /// ```
+    pub fn with_template(self, template: &str) -> std::result::Result<Self, TemplateError> {
+        self.set_template(template)?;
+        Ok(self)
+    }
+
     /// Override the postfix of a progress bar.
     ///
     /// Postfix is usually used for **dynamically** displaying some
@@ -165,17 +370,189 @@ impl AvanceBar {
     pub fn set_postfix(&self, postfix: impl Into<Cow<'static, str>>) {
         let mut state = self.state.lock().unwrap();
         state.template.postfix = Some(postfix.into());
-        let _ = state.draw_to_stderr(None);
+        let _ = state.draw(None);
     }
 
     /// Advance the progress bar by n steps.
     pub fn update(&self, n: u64) {
         self.progress.inc(n);
+        if let Some(parent) = self.summed_by() {
+            parent.update(n);
+        }
 
-        if self.progress.ready() {
-            let _ = self.state.lock().unwrap().draw_to_stderr(None);
+        if self.ready() {
+            let hooks = {
+                let mut state = self.state.lock().unwrap();
+                let _ = state.draw(None);
+                state.progress_hooks()
+            };
             self.progress.update();
+
+            if let Some((hooks, snapshot)) = hooks {
+                hooks.progress(&snapshot);
+            }
+        }
+    }
+
+    /// Set the total length of a progress bar.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(100);
+    /// // Found out the real length halfway
+    /// pb.set_total(200);
+    /// ```
+    pub fn set_total(&self, total: u64) {
+        let mut state = self.state.lock().unwrap();
+        let old = state.total.replace(total).unwrap_or(0);
+        let _ = state.draw(None);
+        drop(state);
+
+        if let Some(parent) = self.summed_by() {
+            parent.shift_total(old, total);
+        }
+    }
+
+    /// Increase the total length of a progress bar by `delta`.
+    ///
+    /// The total length of an unbounded progress bar is regarded as zero.
+    pub fn inc_total(&self, delta: u64) {
+        let mut state = self.state.lock().unwrap();
+        state.total = Some(state.total.unwrap_or(0).saturating_add(delta));
+        let _ = state.draw(None);
+        drop(state);
+
+        if let Some(parent) = self.summed_by() {
+            parent.inc_total(delta);
+        }
+    }
+
+    /// Jump to an absolute position.
+    ///
+    /// The positions jumped over aren't counted when estimating the rate.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000);
+    /// // Resume from a checkpoint
+    /// pb.set_position(400);
+    /// ```
+    pub fn set_position(&self, n: u64) {
+        let mut state = self.state.lock().unwrap();
+        let old = self.progress.n.get();
+        self.progress.set(n);
+        let _ = state.draw(None);
+        drop(state);
+
+        if let Some(parent) = self.summed_by() {
+            parent.shift_position(old, n);
+        }
+    }
+
+    /// Restart a progress bar from zero, including its clock.
+    pub fn reset(&self) {
+        let mut state = self.state.lock().unwrap();
+        let old = self.progress.n.get();
+        self.progress.reset();
+        state.estimator.reset();
+        state.last_sample = None;
+        state.last_log = (Duration::ZERO, 0);
+        let _ = state.draw(None);
+        drop(state);
+
+        if let Some(parent) = self.summed_by() {
+            parent.shift_position(old, 0);
+        }
+    }
+
+    /// Create a child progress bar, which is drawn indented right below this
+    /// progress bar and its other children.
+    ///
+    /// A child shares the draw target of its parent (unless it's a [`DrawTarget::Writer`]),
+    /// and is erased once completed (see [`with_leave`](Self::with_leave)). The parent
+    /// may advance along with its children, see [`with_aggregate`](Self::with_aggregate).
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{Aggregate, AvanceBar};
+    /// let epochs = AvanceBar::new(3).with_aggregate(Aggregate::Count);
+    /// for _ in 0..3 {
+    ///     let batches = epochs.child(100);
+    ///     for _ in batches.with_iter(0..100) {
+    ///         // ...
+    ///     }
+    /// }
+    /// ```
+    pub fn child(&self, total: u64) -> AvanceBar {
+        let (id, aggregate, depth, target, group) = {
+            let state = self.state.lock().unwrap();
+            let target = state
+                .target
+                .as_ref()
+                .map(|target| target.lock().unwrap().share());
+            let group = Arc::clone(&state.group);
+            (
+                state.id,
+                state.template.aggregate,
+                state.depth + 1,
+                target,
+                group,
+            )
+        };
+
+        let mut pb = Self::in_group(Some(total), group);
+        let parent = Arc::new(Parent {
+            bar: self.clone(),
+            aggregate,
+        });
+        pb.parent = Some(Arc::clone(&parent));
+
+        {
+            let mut state = pb.state.lock().unwrap();
+            state.parent = Some(parent);
+            state.depth = depth;
+            state.template.leave = false;
+
+            state.group.nest(state.id, id);
+            if let Some(target) = target {
+                let _ = state.set_target(target);
+            }
+        }
+
+        if aggregate == Aggregate::Sum {
+            self.inc_total(total);
         }
+
+        // Bars below the child were moved down
+        let state = pb.state.lock().unwrap();
+        state.group.redraw_others(state.id);
+        drop(state);
+        pb.refresh();
+        pb
+    }
+
+    /// Builder-like function for a progress bar which advances along with its children
+    /// (default: [`Aggregate::None`]).
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{Aggregate, AvanceBar};
+    /// let repos = AvanceBar::new(0).with_aggregate(Aggregate::Sum);
+    /// let files = repos.child(120);
+    /// // Also advances `repos`
+    /// files.inc();
+    /// ```
+    pub fn with_aggregate(self, aggregate: Aggregate) -> Self {
+        self.set_aggregate(aggregate);
+        self
+    }
+
+    /// Set how a progress bar advances along with its children, which applies to
+    /// children created afterwards.
+    pub fn set_aggregate(&self, aggregate: Aggregate) {
+        self.state.lock().unwrap().template.aggregate = aggregate;
     }
 
     /// Advance the progress bar by one step, with the same effect as
@@ -203,194 +580,1253 @@ impl AvanceBar {
     /// of progress bars, otherwise, progress bars will be closed in the order of being
     /// dropped (Closing order is the same as the rendering order).
     pub fn close(&self) {
-        let _ = self.state.lock().unwrap().close();
+        self.close_with(State::close);
     }
 
-    /// Set the style (default: [`Style::ASCII`]) of a progress bar.
-    pub fn set_style(&self, style: Style) {
-        let mut state = self.state.lock().unwrap();
-        state.template.style = style;
-        let _ = state.draw_to_stderr(None);
+    /// Fill up the progress bar to its total length, and close it.
+    pub fn finish(&self) {
+        self.close_with(|state| {
+            if let Some(total) = state.total {
+                let remaining = total.saturating_sub(self.progress.n.get());
+                self.progress.inc(remaining);
+                if let Some(parent) = self.summed_by() {
+                    parent.update(remaining);
+                }
+            }
+            state.close()
+        });
     }
 
-    /// Set the user-custom style of a progress bar.
-    pub fn set_style_str(&self, s: impl Into<Cow<'static, str>>) {
-        let mut state = self.state.lock().unwrap();
-        state.template.style = Style::Custom(s.into());
-        let _ = state.draw_to_stderr(None);
+    /// Replace the postfix with a final status, and [`finish`](Self::finish) the progress bar.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000);
+    /// // ...
+    /// pb.finish_with_message("done");
+    /// ```
+    pub fn finish_with_message(&self, msg: impl Into<Cow<'static, str>>) {
+        self.state.lock().unwrap().template.postfix = Some(msg.into());
+        self.finish();
     }
 
-    /// Set a progress bar's width
-    pub fn set_width(&self, width: u16) {
-        let mut state = self.state.lock().unwrap();
-        state.template.width = Some(width);
-        let _ = state.clear();
-        let _ = state.draw_to_stderr(None);
+    /// Freeze the progress bar where it is, and mark it as failed.
+    ///
+    /// An abandoned bar is always left on the terminal, see [`with_leave`](Self::with_leave).
+    pub fn abandon(&self) {
+        self.close_with(State::abandon);
     }
 
-    /// Set the description (prefix) of a progress bar.
-    pub fn set_desc(&self, desc: impl Into<Cow<'static, str>>) {
-        let mut state = self.state.lock().unwrap();
-        state.template.desc = Some(desc.into());
-        let _ = state.draw_to_stderr(None);
+    /// Builder-like function for a progress bar which is left on the terminal
+    /// once completed (default: true).
+    ///
+    /// If set to false, the bar is erased when it's closed. Failed bars, which are
+    /// [abandoned](Self::abandon) or dropped during a panic, are always left on the
+    /// terminal.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000).with_leave(false);
+    /// ```
+    pub fn with_leave(self, leave: bool) -> Self {
+        self.set_leave(leave);
+        self
     }
 
-    /// If unit_scale (default: false) is set true, prints the number of iterations
-    /// with an appropriate SI metric prefix.
-    pub fn set_unit_scale(&self, unit_scale: bool) {
-        self.state.lock().unwrap().template.unit_scale = unit_scale;
+    /// Whether to leave the progress bar on the terminal once completed,
+    /// see [`with_leave`](Self::with_leave).
+    pub fn set_leave(&self, leave: bool) {
+        self.state.lock().unwrap().template.leave = leave;
     }
-}
 
-// Private Interface
-impl AvanceBar {
-    /// Creates a progress bar from an iterator's size hint
-    pub(crate) fn with_hint(size_hint: Option<usize>) -> Self {
-        let progress = Arc::new(AtomicProgress::new());
-        AvanceBar {
-            state: Arc::new(Mutex::new(State::new(
-                size_hint.map(|s| s as u64),
-                Arc::clone(&progress),
-            ))),
-            progress,
-        }
+    /// Builder-like function for a progress bar with a draw target
+    /// (default: [`DrawTarget::Stderr`]).
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{AvanceBar, DrawTarget};
+    /// let pb = AvanceBar::new(1000).with_draw_target(DrawTarget::Hidden);
+    /// ```
+    pub fn with_draw_target(self, target: DrawTarget) -> Self {
+        self.set_draw_target(target);
+        self
     }
 
-    /// Refresh the progress bar.
-    fn refresh(&self) {
-        let state = self.state.lock().unwrap();
-        let _ = state.draw_to_stderr(None);
+    /// Set the draw target (default: [`DrawTarget::Stderr`]) of a progress bar.
+    pub fn set_draw_target(&self, target: DrawTarget) {
+        let _ = self.state.lock().unwrap().set_target(target);
     }
-}
 
-#[derive(Debug)]
-struct State {
-    id: ID,
-    progress: Arc<AtomicProgress>,
-    template: Template,
-    total: Option<u64>,
-}
-
-impl State {
-    fn new(total: Option<u64>, progress: Arc<AtomicProgress>) -> Self {
-        Self {
-            id: next_free_pos(),
-            progress,
-            template: Template::new(),
-            total,
-        }
+    /// Builder-like function for a progress bar with an interval between
+    /// two plain lines (default: 10 seconds).
+    ///
+    /// When the draw target is not a terminal (e.g. stderr is redirected to a file),
+    /// a progress bar prints a plain line like `desc: 42% 420/1000 [00:12<00:16, 34.50it/s]`
+    /// every interval, and a final line when being closed.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// # use std::time::Duration;
+    /// let pb = AvanceBar::new(1000).with_log_interval(Duration::from_secs(30));
+    /// ```
+    pub fn with_log_interval(self, interval: Duration) -> Self {
+        self.set_log_interval(interval);
+        self
     }
 
-    fn draw<W: Write>(&self, pos: Option<u16>, target: &mut W) -> Result<()> {
-        if pos.is_none() && !self.drawable() {
-            return Ok(());
-        }
-        let pos = if let Some(pos) = pos {
-            pos
-        } else {
-            self.get_pos()
-        };
-
-        let ncols = terminal_size().0;
-        let nrows = nrows();
-        if pos >= nrows {
-            return Ok(());
-        }
-
-        let msg = if pos == nrows - 1 {
-            "... (more hidden) ...".to_string()
-        } else {
-            format!("{}", self)
-        };
-        let msg = format!("{:1$}", msg, ncols as usize);
-
-        if pos != 0 {
-            target
-                .queue(Print("\n".repeat(pos as usize)))?
-                .queue(Print(msg))?
-                .queue(MoveUp(pos))?
-                .queue(MoveToColumn(ncols))?
-        } else {
-            target.queue(MoveToColumn(0))?.queue(Print(msg))?
-        }
-        .flush()
+    /// Builder-like function for a progress bar which prints a plain line every
+    /// `step` percent on non-interactive targets (default: 0, disabled).
+    ///
+    /// See [`with_log_interval`](Self::with_log_interval)
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// // Print a line at 10%, 20%, ..., 100%
+    /// let pb = AvanceBar::new(1000).with_log_step(10);
+    /// ```
+    pub fn with_log_step(self, step: u8) -> Self {
+        self.set_log_step(step);
+        self
     }
 
-    fn draw_to_stderr(&self, pos: Option<u16>) -> Result<()> {
-        self.draw(pos, &mut stderr().lock())
+    /// Set the spinner of a progress bar, see [`with_spinner`](Self::with_spinner).
+    pub fn set_spinner(&self, spinner: Spinner) {
+        let mut state = self.state.lock().unwrap();
+        state.template.spinner = Some(spinner);
+        let _ = state.draw(None);
     }
 
-    fn drawable(&self) -> bool {
-        // is_terminal is stable on 1.70.0
-        stderr().is_tty() && self.try_get_pos().is_some()
+    /// Whether to show a bouncing animation when the total length is unknown.
+    pub fn set_bounce(&self, bounce: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.template.bounce = bounce;
+        let _ = state.draw(None);
     }
 
-    fn close(&mut self) -> Result<()> {
-        if !self.drawable() {
-            // already closed
-            return Ok(());
-        }
-
-        // Close the current bar and move up other bars
-        reposition(self.id);
-
-        let mut target = stderr().lock();
-
-        // force update (only displaying average its)
+    /// Print a line above all progress bars without corrupting them.
+    ///
+    /// The line is written to the draw target of this progress bar,
+    /// and is dropped if the target is [hidden](DrawTarget::Hidden).
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(100);
+    /// for i in pb.with_iter(0..100) {
+    ///     if i % 10 == 0 {
+    ///         pb.println(format!("checkpoint {}", i));
+    ///     }
+    /// }
+    /// ```
+    pub fn println(&self, msg: impl AsRef<str>) {
+        // Don't lock the state while suspended, as closing bars wait for the screen
+        // with their states locked
+        let (group, target) = {
+            let state = self.state.lock().unwrap();
+            (Arc::clone(&state.group), state.target.clone())
+        };
+        group.suspend(|| {
+            let println = |target: &mut dyn Write| {
+                writeln!(target, "{}", msg.as_ref())?;
+                target.flush()
+            };
+            let _ = match target {
+                Some(target) => target.lock().unwrap().with_writer(println),
+                None => group.with_writer(println),
+            };
+        });
+    }
+
+    /// Take a snapshot of the numbers shown by the progress bar
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(200);
+    /// pb.update(50);
+    ///
+    /// let snapshot = pb.snapshot();
+    /// assert_eq!(snapshot.n, 50);
+    /// assert_eq!(snapshot.percent, Some(25.0));
+    /// ```
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        self.state.lock().unwrap().stats().into()
+    }
+
+    /// Builder-like function to register a callback, which is fired with a
+    /// [snapshot](Self::snapshot) whenever the progress bar is redrawn.
+    ///
+    /// Callbacks are fired in the thread which advances the progress bar, at most
+    /// once per redraw interval (and once more on completion).
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000).on_update(|snapshot| {
+    ///     // Send the metrics somewhere
+    ///     let _ = (snapshot.n, snapshot.rate);
+    /// });
+    /// ```
+    pub fn on_update(self, f: impl FnMut(&ProgressSnapshot) + Send + 'static) -> Self {
+        self.state.lock().unwrap().hooks().on_update(Box::new(f));
+        self
+    }
+
+    /// Builder-like function to register a callback, which is fired every time
+    /// the progress reaches a multiple of `step` percent. The callback receives the
+    /// percentage reached.
+    ///
+    /// Milestones are checked when the progress bar is redrawn, so the progress may
+    /// have gone a bit further than the percentage reached. A milestone is never skipped,
+    /// even when the bar is updated from several threads at once.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000).on_percent(25, |percent, _| {
+    ///     println!("checkpoint at {}%", percent);
+    /// });
+    /// ```
+    pub fn on_percent(
+        self,
+        step: u8,
+        f: impl FnMut(u8, &ProgressSnapshot) + Send + 'static,
+    ) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .hooks()
+            .on_percent(step, Box::new(f));
+        self
+    }
+
+    /// Builder-like function to register a callback, which is fired with the final
+    /// snapshot when the progress bar is closed, either completed or not.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceBar;
+    /// let pb = AvanceBar::new(1000).on_finish(|summary| {
+    ///     println!("{} done in {:?}", summary.n, summary.elapsed);
+    /// });
+    /// ```
+    pub fn on_finish(self, f: impl FnMut(&ProgressSnapshot) + Send + 'static) -> Self {
+        self.state.lock().unwrap().hooks().on_finish(Box::new(f));
+        self
+    }
+
+    /// Builder-like function to register a callback, which is fired once when there's
+    /// no progress for `timeout`, and again only after the progress bar advances.
+    ///
+    /// A stalled progress bar is noticed only when redrawn, so enable the
+    /// [monitor](crate::enable_monitor) to keep redrawing it.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{enable_monitor, AvanceBar};
+    /// # use std::time::Duration;
+    /// enable_monitor(Duration::from_secs(1));
+    /// let pb = AvanceBar::new(1000).on_stall(Duration::from_secs(60), |snapshot| {
+    ///     eprintln!("stuck at {}", snapshot.n);
+    /// });
+    /// ```
+    pub fn on_stall(
+        self,
+        timeout: Duration,
+        f: impl FnMut(&ProgressSnapshot) + Send + 'static,
+    ) -> Self {
+        self.state
+            .lock()
+            .unwrap()
+            .hooks()
+            .on_stall(timeout, Box::new(f));
+        self
+    }
+
+    /// Set the colors of a progress bar, see [`with_colors`](Self::with_colors).
+    pub fn set_colors(&self, colors: Colors) {
+        let mut state = self.state.lock().unwrap();
+        state.template.colors = Some(colors);
+        let _ = state.draw(None);
+    }
+
+    /// Set the rate estimator of a progress bar, see [`with_estimator`](Self::with_estimator).
+    pub fn set_estimator(&self, estimator: impl RateEstimator + 'static) {
+        let mut state = self.state.lock().unwrap();
+        state.estimator = Box::new(estimator);
+        state.last_sample = None;
+        let _ = state.draw(None);
+    }
+
+    /// Set the style (default: [`Style::ASCII`]) of a progress bar.
+    pub fn set_style(&self, style: Style) {
+        let mut state = self.state.lock().unwrap();
+        state.template.style = style;
+        let _ = state.draw(None);
+    }
+
+    /// Set the user-custom style of a progress bar.
+    pub fn set_style_str(&self, s: impl Into<Cow<'static, str>>) {
+        let mut state = self.state.lock().unwrap();
+        state.template.style = Style::Custom(s.into());
+        let _ = state.draw(None);
+    }
+
+    /// Set a progress bar's width
+    pub fn set_width(&self, width: u16) {
+        let mut state = self.state.lock().unwrap();
+        state.template.width = Some(width);
+        let _ = state.clear();
+        let _ = state.draw(None);
+    }
+
+    /// Set the description (prefix) of a progress bar.
+    pub fn set_desc(&self, desc: impl Into<Cow<'static, str>>) {
+        let mut state = self.state.lock().unwrap();
+        state.template.desc = Some(desc.into());
+        let _ = state.draw(None);
+    }
+
+    /// If unit_scale (default: [`UnitScale::None`]) is set, prints the number of iterations
+    /// and the rate with an appropriate prefix.
+    pub fn set_unit_scale(&self, unit_scale: impl Into<UnitScale>) {
+        self.state.lock().unwrap().template.unit_scale = unit_scale.into();
+    }
+
+    /// Set the unit name (default: `it`) of a progress bar.
+    pub fn set_unit(&self, unit: impl Into<Cow<'static, str>>) {
+        let mut state = self.state.lock().unwrap();
+        state.template.unit = unit.into();
+        let _ = state.draw(None);
+    }
+
+    /// Count bytes with the given prefixes, see [`with_bytes`](Self::with_bytes).
+    pub fn set_bytes(&self, unit_scale: UnitScale) {
+        let mut state = self.state.lock().unwrap();
+        state.template.unit_scale = unit_scale;
+        state.template.unit = Cow::Borrowed("B");
+        state.template.bytes = true;
+        let _ = state.draw(None);
+    }
+
+    /// Set the interval between two plain lines on non-interactive targets.
+    pub fn set_log_interval(&self, interval: Duration) {
+        self.state.lock().unwrap().template.log_interval = interval;
+    }
+
+    /// Set the percent step of plain lines on non-interactive targets (0 to disable).
+    pub fn set_log_step(&self, step: u8) {
+        self.state.lock().unwrap().template.log_step = step;
+    }
+
+    /// Set a user-defined template of a progress bar, see [`with_template`](Self::with_template).
+    pub fn set_template(&self, template: &str) -> std::result::Result<(), TemplateError> {
+        let layout = BarTemplate::parse(template)?;
+        let mut state = self.state.lock().unwrap();
+        state.template.layout = Some(layout);
+        let _ = state.clear();
+        let _ = state.draw(None);
+        Ok(())
+    }
+}
+
+// Private Interface
+impl AvanceBar {
+    /// Creates a progress bar from an iterator's size hint
+    pub(crate) fn with_hint(size_hint: Option<usize>) -> Self {
+        Self::with_total(size_hint.map(|s| s as u64))
+    }
+
+    /// Creates a progress bar in the global group, without drawing.
+    fn with_total(total: Option<u64>) -> Self {
+        Self::in_group(total, Arc::clone(Group::global()))
+    }
+
+    /// Creates a progress bar and registers it to a group, without drawing.
+    pub(crate) fn in_group(total: Option<u64>, group: Arc<Group>) -> Self {
+        let progress = Arc::new(AtomicProgress::new(group.is_rendered()));
+        let state = State::new(total, Arc::clone(&progress), Arc::clone(&group));
+        let id = state.id;
+        let state = Arc::new(Mutex::new(state));
+        group.register(id, &state);
+
+        AvanceBar {
+            state,
+            progress,
+            parent: None,
+        }
+    }
+
+    /// The parent which sums up the progress of this bar
+    fn summed_by(&self) -> Option<&AvanceBar> {
+        match &self.parent {
+            Some(parent) if parent.aggregate == Aggregate::Sum => Some(&parent.bar),
+            _ => None,
+        }
+    }
+
+    /// Follow a child whose position changed from `old` to `new`
+    fn shift_position(&self, old: u64, new: u64) {
+        let n = self.progress.n.get();
+        self.set_position((n + new).saturating_sub(old));
+    }
+
+    /// Follow a child whose total length changed from `old` to `new`
+    fn shift_total(&self, old: u64, new: u64) {
+        let total = self.state.lock().unwrap().total.unwrap_or(0);
+        self.set_total((total + new).saturating_sub(old));
+    }
+
+    /// Close the progress bar with `f`, and fire the callbacks on completion
+    /// once the state is unlocked.
+    fn close_with(&self, f: impl FnOnce(&mut State) -> Result<()>) {
+        // Hold the screen before locking the state, the same order as suspending
+        // bars takes them in
+        let group = Arc::clone(&self.state.lock().unwrap().group);
+        let hooks = {
+            let _screen = group.hold_screen();
+            let mut state = self.state.lock().unwrap();
+            let _ = f(&mut state);
+            state.finish_hooks()
+        };
+
+        if let Some((hooks, snapshot)) = hooks {
+            hooks.finish(&snapshot);
+        }
+    }
+
+    /// Whether the progress bar is due to be redrawn by the thread updating it
+    pub(crate) fn ready(&self) -> bool {
+        !self.progress.rendered && self.progress.ready()
+    }
+
+    /// Whether the progress bar is drawn by the render thread of its group
+    #[cfg_attr(not(feature = "rayon"), allow(dead_code))]
+    pub(crate) fn is_rendered(&self) -> bool {
+        self.progress.rendered
+    }
+
+    /// Refresh the progress bar.
+    fn refresh(&self) {
+        let mut state = self.state.lock().unwrap();
+        let _ = state.draw(None);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct State {
+    id: ID,
+    progress: Arc<AtomicProgress>,
+    template: Template,
+    total: Option<u64>,
+    group: Arc<Group>,
+    /// Overrides the draw target of the group
+    target: Option<Arc<Mutex<DrawTarget>>>,
+    estimator: Box<dyn RateEstimator>,
+    /// Elapsed time when the estimator was last fed
+    last_sample: Option<Duration>,
+    closed: bool,
+    /// Whether the bar was abandoned, or dropped during a panic
+    failed: bool,
+    parent: Option<Arc<Parent>>,
+    /// How deep the bar is nested, which decides the indentation
+    depth: u16,
+    /// Elapsed time and progress when the last plain line was printed
+    last_log: (Duration, u64),
+    hooks: Option<Arc<Hooks>>,
+    /// The final snapshot for the callbacks on completion, which haven't been fired
+    finished: Option<ProgressSnapshot>,
+    /// Reused by every frame, so that drawing doesn't allocate
+    buffers: Buffers,
+}
+
+/// The buffers to render a progress bar into
+#[derive(Debug, Default)]
+struct Buffers {
+    /// The line being rendered
+    line: String,
+    /// The line on the screen
+    shown: String,
+    /// Where the shown line was drawn, and how many times the screen had been
+    /// erased by then
+    drawn: Option<(Pos, u64)>,
+    scratch: Scratch,
+    /// The escape sequences and lines to write to the target at once
+    out: String,
+}
+
+/// Space to render the parts of a line in, before they're padded or painted
+#[derive(Debug, Default)]
+struct Scratch {
+    field: String,
+    part: String,
+}
+
+impl Scratch {
+    /// The cleared buffer for a part of the line
+    fn part(&mut self) -> &mut String {
+        self.part.clear();
+        &mut self.part
+    }
+
+    /// Format a part of the line, and write it to the line in the given color
+    fn paint(
+        &mut self,
+        line: &mut String,
+        args: std::fmt::Arguments<'_>,
+        color: Option<Color>,
+    ) -> std::fmt::Result {
+        self.part().write_fmt(args)?;
+        paint(line, &self.part, color)
+    }
+}
+
+impl State {
+    fn new(total: Option<u64>, progress: Arc<AtomicProgress>, group: Arc<Group>) -> Self {
+        let id = next_id();
+        if !group.is_hidden() {
+            group.occupy(id);
+        }
+
+        Self {
+            id,
+            progress,
+            template: Template::new(),
+            total,
+            group,
+            target: None,
+            estimator: Box::new(Smoothed::default()),
+            last_sample: None,
+            closed: false,
+            failed: false,
+            parent: None,
+            depth: 0,
+            last_log: (Duration::ZERO, 0),
+            hooks: None,
+            finished: None,
+            buffers: Buffers::default(),
+        }
+    }
+
+    fn draw(&mut self, pos: Option<u16>) -> Result<()> {
+        self.sample();
+
+        let terminal = self.is_terminal();
+        if pos.is_none() && !terminal {
+            return self.log();
+        }
+        if pos.is_none() && self.try_get_pos().is_none() {
+            return Ok(());
+        }
+        let pos = if let Some(pos) = pos {
+            pos
+        } else {
+            self.get_pos()
+        };
+
+        // Bars suspended by another thread will be redrawn on resumption
+        let group = Arc::clone(&self.group);
+        let _screen = match group.try_hold_screen() {
+            Some(screen) => screen,
+            None => return Ok(()),
+        };
+
+        let (ncols, height) = self.terminal_size();
+        if let Some(old_ncols) = group.resized(ncols) {
+            // Lines drawn at the old width may have wrapped, so erase the whole region
+            // and redraw the other bars at the new width
+            let wrapped = wrapped_rows(old_ncols, ncols);
+            self.with_writer(|target| {
+                if wrapped > 0 {
+                    target.queue(MoveUp(wrapped))?;
+                }
+                target
+                    .queue(MoveToColumn(0))?
+                    .queue(Clear(ClearType::FromCursorDown))?
+                    .flush()
+            })?;
+            group.mark_erased();
+            group.redraw_others(self.id);
+        }
+
+        if !self.frame(pos, (ncols, height), terminal) {
+            return Ok(());
+        }
+
+        // Skip the frame if the same line is still on the screen
+        let drawn = Some((pos, group.erasures()));
+        let buffers = &mut self.buffers;
+        if buffers.drawn == drawn && buffers.line == buffers.shown {
+            return Ok(());
+        }
+        mem::swap(&mut buffers.line, &mut buffers.shown);
+
+        let result = self.write_out(|out, buffers| write_frame(out, pos, &buffers.shown, ncols));
+        self.buffers.drawn = if result.is_ok() { drawn } else { None };
+        result
+    }
+
+    /// Redraw the progress bar regardless of the update interval
+    pub(crate) fn redraw(&mut self) {
+        let _ = self.draw(None);
         self.progress.update();
-        let _ = self.draw(Some(0), &mut target);
+    }
+
+    /// Clear all lines from the progress bar's first line, and move the cursor
+    /// to the beginning of the first line.
+    pub(crate) fn clear_screen(&mut self) -> Result<()> {
+        if !self.drawable() {
+            return Ok(());
+        }
+
+        self.with_writer(|target| {
+            target
+                .queue(MoveToColumn(0))?
+                .queue(Clear(ClearType::FromCursorDown))?
+                .flush()
+        })?;
+        self.group.mark_erased();
+        Ok(())
+    }
+
+    /// Mark the progress bar as failed, and close it
+    pub(crate) fn abandon(&mut self) -> Result<()> {
+        self.failed = true;
+        self.close()
+    }
+
+    fn hooks(&mut self) -> &Hooks {
+        self.hooks.get_or_insert_with(Default::default)
+    }
+
+    /// The callbacks on progress to fire, and the current snapshot
+    pub(crate) fn progress_hooks(&self) -> Option<(Arc<Hooks>, ProgressSnapshot)> {
+        let hooks = Arc::clone(self.hooks.as_ref()?);
+        Some((hooks, self.stats().into()))
+    }
+
+    /// The callbacks on completion to fire, and the final snapshot
+    fn finish_hooks(&mut self) -> Option<(Arc<Hooks>, ProgressSnapshot)> {
+        let snapshot = self.finished.take()?;
+        Some((Arc::clone(self.hooks.as_ref()?), snapshot))
+    }
+
+    pub(crate) fn id(&self) -> ID {
+        self.id
+    }
+
+    /// Run `f` with exclusive access to the draw target of the progress bar
+    pub(crate) fn with_writer<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<()>,
+    {
+        match &self.target {
+            Some(target) => target.lock().unwrap().with_writer(f),
+            None => self.group.with_writer(f),
+        }
+    }
+
+    /// Compose the output with `f` in a reused buffer, and write it to the draw
+    /// target at once
+    fn write_out<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut String, &Buffers) -> std::fmt::Result,
+    {
+        let mut out = mem::take(&mut self.buffers.out);
+        out.clear();
+        // Writing to a string never fails
+        let _ = f(&mut out, &self.buffers);
+
+        let result = self.with_writer(|target| {
+            target.write_all(out.as_bytes())?;
+            target.flush()
+        });
+        self.buffers.out = out;
+        result
+    }
+
+    fn is_terminal(&self) -> bool {
+        match &self.target {
+            Some(target) => target.lock().unwrap().is_terminal(),
+            None => self.group.is_terminal(),
+        }
+    }
+
+    /// The size of the terminal which the bar is drawn to, in columns and rows
+    fn terminal_size(&self) -> (u16, u16) {
+        match &self.target {
+            Some(target) => {
+                let fixed = target.lock().unwrap().fixed_size();
+                fixed.unwrap_or_else(terminal_size)
+            }
+            None => self.group.terminal_size(),
+        }
+    }
+
+    fn is_hidden(&self) -> bool {
+        match &self.target {
+            Some(target) => target.lock().unwrap().is_hidden(),
+            None => self.group.is_hidden(),
+        }
+    }
+
+    /// Render the line at the given position into the line buffer, padded to the
+    /// width of a terminal of the given size. Returns false if the position is out
+    /// of the screen.
+    fn frame(&mut self, pos: Pos, (ncols, height): (u16, u16), terminal: bool) -> bool {
+        let nrows = self.group.nrows_in(height);
+        if pos >= nrows {
+            self.buffers.drawn = None;
+            return false;
+        }
+
+        let mut buffers = mem::take(&mut self.buffers);
+        let line = &mut buffers.line;
+        line.clear();
+        if pos == nrows - 1 {
+            line.push_str("... (more hidden) ...");
+        } else {
+            // Writing to a string never fails
+            let _ = self.render(line, &mut buffers.scratch, ncols, terminal);
+        }
+        // Pad with spaces to overwrite the last frame, excluding colors from the width
+        let padding = (ncols as usize).saturating_sub(display_width(line));
+        line.extend(iter::repeat(' ').take(padding));
+
+        self.buffers = buffers;
+        true
+    }
+
+    /// Print a plain line to a non-interactive target, if enough time
+    /// or progress has passed since the last line.
+    fn log(&mut self) -> Result<()> {
+        if self.is_hidden() || self.try_get_pos().is_none() {
+            return Ok(());
+        }
+
+        let elapsed = self.progress.elapsed();
+        let n = self.progress.n.get();
+        let (last_elapsed, last_n) = self.last_log;
+
+        let time_due = elapsed.saturating_sub(last_elapsed) >= self.template.log_interval;
+        let step_due = match (self.template.log_step, self.total) {
+            (0, _) | (_, None) | (_, Some(0)) => false,
+            (step, Some(total)) => log_step_due(step, total, last_n, n),
+        };
+        if !time_due && !step_due {
+            return Ok(());
+        }
+
+        self.last_log = (elapsed, n);
+        let line = self.log_line();
+        self.with_writer(|target| {
+            writeln!(target, "{}", line)?;
+            target.flush()
+        })
+    }
+
+    /// Feed the rate estimator with the current progress, at most once per update
+    /// interval, as setters may redraw the bar much more often
+    fn sample(&mut self) {
+        let elapsed = self.progress.elapsed();
+        if let Some(last) = self.last_sample {
+            if elapsed.saturating_sub(last) < Duration::from_nanos(INTERVAL) {
+                return;
+            }
+        }
+
+        self.last_sample = Some(elapsed);
+        self.estimator.record(self.progress.processed(), elapsed);
+    }
+
+    fn drawable(&self) -> bool {
+        self.is_terminal() && self.try_get_pos().is_some()
+    }
+
+    /// Close the progress bar, while the caller holds the screen of its group.
+    fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+
+        self.closed = true;
+        if let Some(parent) = &self.parent {
+            if parent.aggregate == Aggregate::Count && !self.failed {
+                parent.bar.inc();
+            }
+        }
+        if self.hooks.is_some() {
+            self.finished = Some(self.stats().into());
+        }
+
+        if self.try_get_pos().is_none() {
+            // A hidden bar draws nothing
+            return Ok(());
+        }
+
+        let terminal = self.is_terminal();
+        if !terminal {
+            self.group.reposition(self.id);
+
+            let line = self.log_line();
+            return self.with_writer(|target| {
+                writeln!(target, "{}", line)?;
+                target.flush()
+            });
+        }
+
+        // Close the current bar and move up other bars
+        let group = Arc::clone(&self.group);
+        group.reposition(self.id);
+
+        if !self.template.leave && !self.failed {
+            // Erase all bars, and redraw the others at their new positions
+            self.with_writer(|target| {
+                target
+                    .queue(MoveToColumn(0))?
+                    .queue(Clear(ClearType::FromCursorDown))?
+                    .flush()
+            })?;
+            group.mark_erased();
+            group.redraw_others(self.id);
+            return Ok(());
+        }
+
+        let (ncols, height) = self.terminal_size();
+        let visible = self.frame(0, (ncols, height), terminal);
+        let finished = group.is_finished();
+
+        self.write_out(|out, buffers| {
+            if visible {
+                write_frame(out, 0, &buffers.line, ncols)?;
+            }
+            // Move cursor to the end of the next line
+            out.push('\n');
+            if !finished {
+                // only do this when some bars are still in progress
+                MoveToColumn(ncols).write_ansi(out)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Sweep a progress bar from the terminal.
+    /// Useful when a progress bar's width was changed.
+    fn clear(&mut self) -> Result<()> {
+        if !self.drawable() {
+            return Ok(());
+        }
+        let group = Arc::clone(&self.group);
+        let _screen = match group.try_hold_screen() {
+            Some(screen) => screen,
+            None => return Ok(()),
+        };
+
+        let pos = self.get_pos();
+        let nrows = group.nrows_in(self.terminal_size().1);
+        if pos >= nrows {
+            return Ok(());
+        }
+
+        self.write_out(|out, _| {
+            out.extend(iter::repeat('\n').take(pos as usize));
+            Clear(ClearType::CurrentLine).write_ansi(out)?;
+            if pos != 0 {
+                MoveUp(pos).write_ansi(out)?;
+            }
+            Ok(())
+        })?;
+        self.buffers.drawn = None;
+        Ok(())
+    }
+
+    /// Change the draw target, moving the bar from the old target to the new one.
+    fn set_target(&mut self, target: DrawTarget) -> Result<()> {
+        self.clear()?;
+
+        if target.is_hidden() {
+            // A hidden bar doesn't take up a line
+            if self.try_get_pos().is_some() {
+                self.group.reposition(self.id);
+            }
+        } else if self.is_hidden() {
+            self.group.occupy(self.id);
+        }
+        self.target = Some(Arc::new(Mutex::new(target)));
+
+        self.draw(None)
+    }
+
+    fn try_get_pos(&self) -> Option<Pos> {
+        self.group.position(self.id)
+    }
+
+    fn get_pos(&self) -> Pos {
+        self.try_get_pos().unwrap()
+    }
+}
+
+/// Whether the progress has crossed a multiple of `step` percent since `last_n`
+fn log_step_due(step: u8, total: u64, last_n: u64, n: u64) -> bool {
+    let percent = |n: u64| n as u128 * 100 / total as u128;
+    let step = step as u128;
+    percent(n) / step > percent(last_n) / step
+}
+
+/// Write a rendered line at the given position to `out`, which leaves the cursor
+/// at the first line.
+fn write_frame(out: &mut String, pos: Pos, msg: &str, ncols: u16) -> std::fmt::Result {
+    if pos != 0 {
+        // Line feeds scroll the terminal when bars reach its bottom, unlike moving down
+        out.extend(iter::repeat('\n').take(pos as usize));
+        out.push_str(msg);
+        MoveUp(pos).write_ansi(out)?;
+        MoveToColumn(ncols).write_ansi(out)
+    } else {
+        MoveToColumn(0).write_ansi(out)?;
+        out.push_str(msg);
+        Ok(())
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut line = String::new();
+        let (ncols, terminal) = (self.terminal_size().0, self.is_terminal());
+        self.render(&mut line, &mut Scratch::default(), ncols, terminal)?;
+        fmt.write_str(&line)
+    }
+}
+
+impl State {
+    /// Render the progress bar into `line` for a terminal `ncols` wide, using `scratch`
+    /// for the parts of it. Colors are only rendered if the target is a terminal.
+    fn render(
+        &self,
+        line: &mut String,
+        scratch: &mut Scratch,
+        ncols: u16,
+        terminal: bool,
+    ) -> std::fmt::Result {
+        let width = self.template.width.map_or(ncols, |w| min(w, ncols));
+
+        // Indent nested bars under their parents
+        let indent = 2 * self.depth;
+        let width = width.saturating_sub(indent) as usize;
+        line.extend(iter::repeat(' ').take(indent as usize));
+
+        let stats = self.stats();
+        let colors = self.colors(terminal);
+
+        if let Some(layout) = &self.template.layout {
+            return self.render_layout(line, scratch, layout, width, &stats, colors);
+        }
 
-        // Move cursor to the end of the next line
-        let ncols = terminal_size().0;
+        let Stats {
+            n, elapsed, its, ..
+        } = stats;
+        let time = Time(elapsed as u64);
+        let postfix = self.template.postfix.as_deref();
 
-        target.queue(Print('\n'))?;
-        if !is_finished() {
-            // only do this when some bars are still in progress
-            target.queue(MoveToColumn(ncols))?;
+        let desc_color = colors.and_then(Colors::desc);
+        let stats_color = colors.and_then(Colors::stats);
+        let filled_color = colors.and_then(|c| c.filled(stats.pct(), self.status(&stats)));
+
+        // The bar takes up the width left by everything from here
+        let start = line.len();
+        if let Some(desc) = &self.template.desc {
+            scratch.paint(line, format_args!("{}: ", desc), desc_color)?;
         }
-        target.flush()
+
+        let at = match self.total {
+            None => {
+                if let Some(spinner) = &self.template.spinner {
+                    let frame = spinner.frame(stats.tick());
+                    scratch.paint(line, format_args!("{} ", frame), filled_color)?;
+                }
+
+                let at = match self.template.bounce {
+                    true => {
+                        line.push('|');
+                        let at = line.len();
+                        line.push('|');
+                        Some(at)
+                    }
+                    false => None,
+                };
+
+                let r_bar = scratch.part();
+                if at.is_some() {
+                    r_bar.push(' ');
+                }
+                write!(
+                    r_bar,
+                    "{} [{}, {}]",
+                    self.count_with_unit(n),
+                    time,
+                    self.rate(its)
+                )?;
+                if let Some(postfix) = postfix {
+                    write!(r_bar, ", {}", postfix)?;
+                }
+                paint(line, &scratch.part, stats_color)?;
+
+                match at {
+                    Some(at) => at,
+                    None => return Ok(()),
+                }
+            }
+
+            Some(total) => {
+                let percent = (100.0 * stats.pct().unwrap()) as u64;
+                scratch.paint(line, format_args!("{:>3}%", percent), stats_color)?;
+                line.push('|');
+                let at = line.len();
+                line.push('|');
+
+                let r_bar = scratch.part();
+                write!(
+                    r_bar,
+                    " {}/{} [{}<{}, {}",
+                    self.count(n),
+                    self.count(total),
+                    time,
+                    OrUnknown(stats.eta().map(Time)),
+                    self.rate(its)
+                )?;
+                if let Some(postfix) = postfix {
+                    write!(r_bar, ", {}", postfix)?;
+                }
+                r_bar.push(']');
+                paint(line, &scratch.part, stats_color)?;
+
+                at
+            }
+        };
+
+        let limit = width.saturating_sub(display_width(&line[start..]));
+        self.insert_bar(line, at, scratch, &stats, limit, colors)
     }
 
-    /// Sweep a progress bar from the terminal.
-    /// Useful when a progress bar's width was changed.
-    fn clear(&self) -> Result<()> {
-        if !self.drawable() {
-            return Ok(());
+    /// Collect the statistics to display
+    fn stats(&self) -> Stats {
+        let elapsed = self.progress.elapsed().as_secs_f64();
+
+        let n = self.progress.n.get();
+        let average = self.progress.processed() as f64 / elapsed;
+
+        // Only display the average rate when closed
+        let its = match self.closed {
+            true => average,
+            false => self.estimator.rate().unwrap_or(average),
+        };
+
+        Stats {
+            n,
+            total: self.total,
+            elapsed,
+            its,
         }
+    }
 
-        let mut target = stderr().lock();
-        let pos = self.get_pos();
-        let nrows = nrows();
-        if pos >= nrows {
-            return Ok(());
+    /// A number of iterations
+    fn count(&self, n: u64) -> Count<'_> {
+        Count {
+            n,
+            template: &self.template,
+            unit: self.template.bytes,
         }
+    }
 
-        if pos != 0 {
-            target
-                .queue(Print("\n".repeat(pos as usize)))?
-                .queue(Clear(ClearType::CurrentLine))?
-                .queue(MoveUp(pos))?
-        } else {
-            target.queue(Clear(ClearType::CurrentLine))?
+    /// A number of iterations along with the unit, for unbounded progress bars
+    fn count_with_unit(&self, n: u64) -> Count<'_> {
+        Count {
+            n,
+            template: &self.template,
+            unit: true,
         }
-        .flush()
     }
 
-    fn try_get_pos(&self) -> Option<Pos> {
-        let positions = positions().lock().unwrap();
-        positions.get(&self.id).copied()
+    /// The number of iterations per second, or seconds per iteration
+    /// if it's slower than one iteration per second.
+    fn rate(&self, its: f64) -> Rate<'_> {
+        Rate {
+            its,
+            template: &self.template,
+        }
     }
 
-    fn get_pos(&self) -> Pos {
-        self.try_get_pos().unwrap()
+    /// Render a progress bar with a user-defined template
+    fn render_layout(
+        &self,
+        line: &mut String,
+        scratch: &mut Scratch,
+        layout: &BarTemplate,
+        width: usize,
+        stats: &Stats,
+        colors: Option<&Colors>,
+    ) -> std::fmt::Result {
+        use template::{Key, Segment};
+
+        let pct = stats.pct();
+
+        // The number of displayed characters, excluding colors
+        let mut line_width = 0;
+        // Where to insert the bar which takes up the remaining space
+        let mut flexible = None;
+
+        for segment in &layout.segments {
+            let (key, spec) = match segment {
+                Segment::Literal(s) => {
+                    line.push_str(s);
+                    line_width += s.chars().count();
+                    continue;
+                }
+                Segment::Field { key, spec } => (key, spec),
+            };
+
+            let field = &mut scratch.field;
+            field.clear();
+            match key {
+                Key::Bar => match spec.width {
+                    Some(limit) => self.render_bar(field, stats, limit),
+                    None => {
+                        flexible = Some(line.len());
+                        continue;
+                    }
+                },
+                Key::Desc => field.push_str(self.template.desc.as_deref().unwrap_or("")),
+                Key::Pos => write!(field, "{}", self.count(stats.n))?,
+                Key::Len => write!(field, "{}", OrUnknown(self.total.map(|t| self.count(t))))?,
+                Key::Percent => write!(field, "{}", OrUnknown(pct.map(|p| (100.0 * p) as u64)))?,
+                Key::Elapsed => write!(field, "{}", Time(stats.elapsed as u64))?,
+                Key::Eta => write!(field, "{}", OrUnknown(stats.eta().map(Time)))?,
+                Key::Rate => write!(field, "{}", self.rate(stats.its))?,
+                Key::Postfix => field.push_str(self.template.postfix.as_deref().unwrap_or("")),
+                Key::Spinner => field.push_str(
+                    self.template
+                        .spinner
+                        .as_ref()
+                        .unwrap_or(&Spinner::default())
+                        .frame(stats.tick()),
+                ),
+            }
+
+            let padded = &mut scratch.part;
+            padded.clear();
+            spec.pad(&scratch.field, padded);
+            line_width += padded.chars().count();
+
+            match (key, colors) {
+                (_, None) => line.push_str(padded),
+                (Key::Bar, Some(colors)) => self.paint_bar(line, padded, stats, colors)?,
+                (Key::Desc, Some(colors)) => paint(line, padded, colors.desc())?,
+                (Key::Spinner, Some(colors)) => {
+                    paint(line, padded, colors.filled(pct, self.status(stats)))?;
+                }
+                (_, Some(colors)) => paint(line, padded, colors.stats())?,
+            }
+        }
+
+        match flexible {
+            Some(at) => {
+                let limit = width.saturating_sub(line_width);
+                self.insert_bar(line, at, scratch, stats, limit, colors)
+            }
+            None => Ok(()),
+        }
     }
-}
 
-impl Display for State {
-    fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
-        use format::*;
+    /// Render the bar part of a progress bar, which bounces if the total is unknown.
+    fn render_bar(&self, out: &mut String, stats: &Stats, limit: usize) {
+        match stats.pct() {
+            Some(pct) => render_bar(out, &self.template.style, pct, limit),
+            None => render_bounce(out, &self.template.style, stats.tick(), limit),
+        }
+    }
+
+    /// Render a bar which is `limit` characters long, and insert it into the line at `at`
+    fn insert_bar(
+        &self,
+        line: &mut String,
+        at: usize,
+        scratch: &mut Scratch,
+        stats: &Stats,
+        limit: usize,
+        colors: Option<&Colors>,
+    ) -> std::fmt::Result {
+        let Scratch { field, part } = scratch;
+        field.clear();
+        self.render_bar(field, stats, limit);
+
+        let bar = match colors {
+            Some(colors) => {
+                part.clear();
+                self.paint_bar(part, field, stats, colors)?;
+                part
+            }
+            None => field,
+        };
+        line.insert_str(at, bar);
+        Ok(())
+    }
+
+    /// Paint the filled part and the background part of a rendered bar
+    fn paint_bar(
+        &self,
+        out: &mut String,
+        bar: &str,
+        stats: &Stats,
+        colors: &Colors,
+    ) -> std::fmt::Result {
+        let background = self.template.style.as_ref().chars().last().unwrap();
+        let filled = colors.filled(stats.pct(), self.status(stats));
+
+        let mut rest = bar;
+        while let Some(c) = rest.chars().next() {
+            let is_background = c == background;
+            let end = rest
+                .find(|c| (c == background) != is_background)
+                .unwrap_or(rest.len());
+            let color = match is_background {
+                true => colors.background(),
+                false => filled,
+            };
+
+            paint(out, &rest[..end], color)?;
+            rest = &rest[end..];
+        }
+
+        Ok(())
+    }
+
+    /// Whether the bar is in progress, done or failed
+    fn status(&self, stats: &Stats) -> Status {
+        if self.failed {
+            Status::Failed
+        } else if self.closed || stats.pct().map_or(false, |pct| pct >= 1.0) {
+            Status::Done
+        } else {
+            Status::InProgress
+        }
+    }
+
+    /// The colors to render with, or None if colors are disabled
+    fn colors(&self, terminal: bool) -> Option<&Colors> {
+        match no_color() || !terminal {
+            true => None,
+            false => self.template.colors.as_ref(),
+        }
+    }
 
-        let elapsed = self.progress.begin.elapsed().as_secs_f64();
+    /// Render a plain line for non-interactive outputs, such as
+    /// `desc: 42% 420/1000 [00:12<00:16, 34.50it/s]`
+    fn log_line(&self) -> String {
+        let stats = self.stats();
         let desc = self
             .template
             .desc
@@ -401,113 +1837,226 @@ impl Display for State {
             .postfix
             .as_ref()
             .map_or_else(String::new, |p| format!(", {}", p));
+        let time = Time(stats.elapsed as u64);
 
-        let terminal_width = terminal::size().map_or(80, |(c, _)| c);
-        let width = self
-            .template
-            .width
-            .map_or(terminal_width, |w| min(w, terminal_width));
-
-        let n = self.progress.n.load(Ordering::Relaxed);
-        let last_n = self.progress.last.load(Ordering::Relaxed);
-        let since_last = self.progress.since_last() as f64 / 1e9;
-
-        // smoothing
-        let factor = 0.7;
-        let its = match n - last_n {
-            0 => n as f64 / elapsed,
-            gap => (n as f64 / elapsed) * factor + (gap as f64 / since_last) * (1.0 - factor),
-        };
+        match self.total {
+            None => format!(
+                "{}{} [{}, {}{}]",
+                desc,
+                self.count_with_unit(stats.n),
+                time,
+                self.rate(stats.its),
+                postfix
+            ),
+            Some(total) => format!(
+                "{}{}% {}/{} [{}<{}, {}{}]",
+                desc,
+                (100.0 * stats.pct().unwrap()) as u64,
+                self.count(stats.n),
+                self.count(total),
+                time,
+                OrUnknown(stats.eta().map(Time)),
+                self.rate(stats.its),
+                postfix
+            ),
+        }
+    }
+}
 
-        let time = format_time(elapsed as u64);
+/// Statistics of a progress bar at some moment
+struct Stats {
+    n: u64,
+    total: Option<u64>,
+    /// Elapsed time in seconds
+    elapsed: f64,
+    /// Iterations per second
+    its: f64,
+}
+
+impl Stats {
+    /// Finished fraction, from 0 to 1
+    fn pct(&self) -> Option<f64> {
+        self.total
+            .map(|total| (self.n as f64 / total as f64).clamp(0.0, 1.0))
+    }
 
+    /// The number of animation frames since the beginning
+    fn tick(&self) -> usize {
+        (self.elapsed * 1e9) as usize / INTERVAL as usize
+    }
+
+    /// Estimated remaining time in seconds
+    fn eta(&self) -> Option<u64> {
         match self.total {
-            None => fmt.write_fmt(format_args!(
-                "{}{}it [{}, {:.02}it/s]{}",
-                desc, n, time, its, postfix
-            )),
+            Some(total) if self.n > 0 && self.its.is_finite() && self.its > 0.0 => {
+                Some((total.saturating_sub(self.n) as f64 / self.its) as u64)
+            }
+            _ => None,
+        }
+    }
+}
 
-            Some(total) => {
-                let pct = (n as f64 / total as f64).clamp(0.0, 1.0);
-                let eta = match n {
-                    0 => String::from("?"),
-                    _ => format_time((elapsed / pct * (1. - pct)) as u64),
-                };
+/// Paint a piece of text with a foreground color, and write it to `out`
+fn paint(out: &mut String, s: &str, color: Option<Color>) -> std::fmt::Result {
+    match color {
+        Some(color) if !s.is_empty() => write!(out, "{}", s.with(color)),
+        _ => {
+            out.push_str(s);
+            Ok(())
+        }
+    }
+}
 
-                let l_bar = format!("{}{:>3}%|", desc, (100.0 * pct) as u64);
-                let r_bar = match self.template.unit_scale {
-                    true => format!(
-                        "| {}/{} [{}<{}, {:.02}it/s{}]",
-                        format_sizeof(n),
-                        format_sizeof(total),
-                        time,
-                        eta,
-                        its,
-                        postfix
-                    ),
-                    false => format!(
-                        "| {}/{} [{}<{}, {:.02}it/s{}]",
-                        n, total, time, eta, its, postfix
-                    ),
-                };
-                let limit = (width as usize).saturating_sub(l_bar.len() + r_bar.len());
+/// Render a block bouncing back and forth, which is `limit` characters long.
+fn render_bounce(out: &mut String, style: &Style, tick: usize, limit: usize) {
+    let mut chars = style.as_ref().chars();
+    let filled = chars.next().unwrap();
+    let background = chars.last().unwrap();
+
+    let width = max(limit / 8, 1).min(limit);
+    let span = limit - width;
+    let pos = match span {
+        0 => 0,
+        span => {
+            let p = tick % (2 * span);
+            min(p, 2 * span - p)
+        }
+    };
 
-                let style: Vec<_> = self.template.style.as_ref().chars().collect();
+    out.extend(iter::repeat(background).take(pos));
+    out.extend(iter::repeat(filled).take(width));
+    out.extend(iter::repeat(background).take(limit - pos - width));
+}
 
-                let filled = style[0];
-                let (background, in_progress) = style[1..].split_last().unwrap();
+/// Render the bar part of a progress bar, which is `limit` characters long.
+fn render_bar(out: &mut String, style: &Style, pct: f64, limit: usize) {
+    let mut chars = style.as_ref().chars();
+    let filled = chars.next().unwrap();
+    let background = chars.next_back().unwrap();
+    // The characters left are for the cell in progress
+    let mut in_progress = chars;
 
-                let m = in_progress.len();
-                let k = ((limit as f64 * pct) * m as f64) as usize;
-                let n_filled = k / m;
-                let current = k % m;
+    let m = in_progress.clone().count();
+    let k = ((limit as f64 * pct) * m as f64) as usize;
+    let n_filled = k / m;
+    let current = k % m;
 
-                let mut bar = filled.to_string().repeat(n_filled);
+    out.extend(iter::repeat(filled).take(n_filled));
 
-                if n_filled < limit {
-                    bar.push(in_progress[current]);
-                }
+    if n_filled < limit {
+        out.push(in_progress.nth(current).unwrap());
+    }
 
-                // Unicode width is not considered at the moment
-                if n_filled + 1 < limit {
-                    let n_padding = limit - n_filled - 1;
-                    let padding = background.to_string().repeat(n_padding);
+    // Unicode width is not considered at the moment
+    if n_filled + 1 < limit {
+        out.extend(iter::repeat(background).take(limit - n_filled - 1));
+    }
+}
 
-                    bar.push_str(&padding);
-                }
+/// A number of iterations, displayed in the unit scale of a progress bar
+struct Count<'a> {
+    n: u64,
+    template: &'a Template,
+    /// Whether to display the unit
+    unit: bool,
+}
 
-                fmt.write_fmt(format_args!("{}{}{}", l_bar, bar, r_bar))
-            }
+impl Display for Count<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.template.unit_scale {
+            UnitScale::None => write!(f, "{}", self.n)?,
+            scale => write!(f, "{}", Scaled(self.n as f64, scale))?,
+        }
+
+        match self.unit {
+            true => f.write_str(&self.template.unit),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The number of iterations per second, or seconds per iteration if it's slower
+struct Rate<'a> {
+    its: f64,
+    template: &'a Template,
+}
+
+impl Display for Rate<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let unit = &self.template.unit;
+        if self.its > 0.0 && self.its < 1.0 {
+            return write!(f, "{:.2}s/{}", 1.0 / self.its, unit);
         }
+
+        write!(
+            f,
+            "{}{}/s",
+            Scaled(self.its, self.template.unit_scale),
+            unit
+        )
     }
 }
 
 impl Drop for State {
     fn drop(&mut self) {
+        if thread::panicking() {
+            self.failed = true;
+        }
+        let group = Arc::clone(&self.group);
+        let screen = group.hold_screen();
         drop(self.close());
+        drop(screen);
+        group.unregister(self.id);
+
+        if let Some((hooks, snapshot)) = self.finish_hooks() {
+            hooks.finish(&snapshot);
+        }
     }
 }
 
 #[derive(Debug)]
 struct AtomicProgress {
-    begin: Instant,
+    /// A fixed point in time, from which all timestamps (in nanoseconds) are measured
+    anchor: Instant,
+    begin: AtomicU64,
     prev: AtomicU64,
-    last: AtomicU64,
-    n: AtomicU64,
+    n: ShardedCounter,
+    /// Positions jumped over (or back) by setting the position directly
+    skipped: AtomicI64,
+    /// Whether the progress bar is drawn by the render thread of its group
+    rendered: bool,
 }
 
 impl AtomicProgress {
-    fn new() -> Self {
+    fn new(rendered: bool) -> Self {
         Self {
-            begin: Instant::now(),
+            anchor: Instant::now(),
+            begin: AtomicU64::new(0),
             prev: AtomicU64::new(0),
-            last: AtomicU64::new(0),
-            n: AtomicU64::new(0),
+            n: ShardedCounter::default(),
+            skipped: AtomicI64::new(0),
+            rendered,
         }
     }
 
     fn inc(&self, delta: u64) {
-        self.n.fetch_add(delta, Ordering::AcqRel);
+        self.n.add(delta);
+    }
+
+    fn set(&self, n: u64) {
+        let old = self.n.set(n);
+        self.skipped
+            .fetch_add(n as i64 - old as i64, Ordering::AcqRel);
+        self.update();
+    }
+
+    /// Restart from zero, and restart the clock
+    fn reset(&self) {
+        let now = self.now();
+        self.begin.store(now, Ordering::Release);
+        self.n.set(0);
+        self.skipped.store(0, Ordering::Release);
+        self.update();
     }
 
     fn ready(&self) -> bool {
@@ -515,16 +2064,28 @@ impl AtomicProgress {
     }
 
     fn update(&self) {
-        self.prev
-            .store(self.begin.elapsed().as_nanos() as u64, Ordering::Release);
-        self.last
-            .store(self.n.load(Ordering::Acquire), Ordering::Release);
+        self.prev.store(self.now(), Ordering::Release);
+    }
+
+    /// The number of iterations which were actually processed, excluding
+    /// the positions jumped over by set_position.
+    fn processed(&self) -> u64 {
+        let n = self.n.get() as i64;
+        (n - self.skipped.load(Ordering::Acquire)).max(0) as u64
     }
 
     fn since_last(&self) -> u64 {
         let prev = self.prev.load(Ordering::Acquire);
-        let since_begin = self.begin.elapsed().as_nanos() as u64;
-        since_begin.saturating_sub(prev)
+        self.now().saturating_sub(prev)
+    }
+
+    fn elapsed(&self) -> Duration {
+        let begin = self.begin.load(Ordering::Acquire);
+        Duration::from_nanos(self.now().saturating_sub(begin))
+    }
+
+    fn now(&self) -> u64 {
+        self.anchor.elapsed().as_nanos() as u64
     }
 }
 
@@ -533,8 +2094,20 @@ struct Template {
     style: Style,
     width: Option<u16>,
     desc: Option<Cow<'static, str>>,
-    unit_scale: bool,
+    unit: Cow<'static, str>,
+    unit_scale: UnitScale,
+    /// Whether to show the unit along with counts, such as `12.3MiB/1.00GiB`
+    bytes: bool,
     postfix: Option<Cow<'static, str>>,
+    layout: Option<BarTemplate>,
+    log_interval: Duration,
+    log_step: u8,
+    spinner: Option<Spinner>,
+    bounce: bool,
+    colors: Option<Colors>,
+    /// Whether to leave the bar on the terminal once completed
+    leave: bool,
+    aggregate: Aggregate,
 }
 
 impl Template {
@@ -543,88 +2116,101 @@ impl Template {
             style: Default::default(),
             width: None,
             desc: None,
-            unit_scale: false,
+            unit: Cow::Borrowed("it"),
+            unit_scale: UnitScale::None,
+            bytes: false,
             postfix: None,
+            layout: None,
+            log_interval: Duration::from_secs(10),
+            log_step: 0,
+            spinner: None,
+            bounce: false,
+            colors: None,
+            leave: true,
+            aggregate: Aggregate::None,
         }
     }
 }
 
-type AtomicState = Arc<Mutex<State>>;
-type ID = u64;
-type Pos = u16;
+/// The parent of a nested progress bar
+#[derive(Debug)]
+struct Parent {
+    bar: AvanceBar,
+    aggregate: Aggregate,
+}
 
 // Minimun update interval (in nanoseconds)
 const INTERVAL: u64 = 100_000_000;
 
-// Next unused ID
-static NEXTID: AtomicU64 = AtomicU64::new(0);
-// How many rows are progress bars allowed to use. If unspecified,
-// use the terminal height.
-static NROWS: AtomicU16 = AtomicU16::new(0);
-// Book-keeping the positions of all bars.
-static POSITIONS: OnceLock<Mutex<HashMap<ID, Pos>>> = OnceLock::new();
-
 /// Set how many on-going progress bar can be shown on the screen.
 ///
 /// If specified, hides bars outside this limit. If unspecified, adjusts to
 /// the terminal height.
+///
+/// Only progress bars created without a [`MultiAvance`](crate::MultiAvance) are affected.
 pub fn set_max_progress_bars(nbars: u16) {
-    let nrows = max(nbars + 1, 2);
-    NROWS.swap(nrows, Ordering::Relaxed);
+    Group::global().set_max_progress_bars(nbars);
 }
 
-fn positions() -> &'static Mutex<HashMap<ID, Pos>> {
-    POSITIONS.get_or_init(|| Mutex::new(HashMap::new()))
-}
-
-// Whether all progress bars are closed or not
-fn is_finished() -> bool {
-    positions().lock().unwrap().is_empty()
+/// Clear all progress bars, run `f`, and redraw the bars below its output.
+///
+/// Progress bars aren't drawn until `f` returns, so it's safe to write to
+/// the terminal in `f`, such as printing logs or prompting for input.
+///
+/// Only progress bars created without a [`MultiAvance`](crate::MultiAvance) are suspended,
+/// see [`MultiAvance::suspend`](crate::MultiAvance::suspend) for the others.
+///
+/// # Examples
+/// ```
+/// # use avance::{suspend, AvanceBar};
+/// let pb = AvanceBar::new(100);
+/// suspend(|| eprintln!("something happened"));
+/// ```
+pub fn suspend<F: FnOnce() -> R, R>(f: F) -> R {
+    Group::global().suspend(f)
 }
 
-fn terminal_size() -> (u16, u16) {
-    crossterm::terminal::size().unwrap_or((80, 64))
+/// Print a line to the standard output above all progress bars,
+/// like `tqdm.write` does.
+///
+/// # Examples
+/// ```
+/// # use avance::AvanceBar;
+/// let pb = AvanceBar::new(100);
+/// for i in pb.with_iter(0..100) {
+///     if i == 42 {
+///         avance::println("found the answer");
+///     }
+/// }
+/// ```
+pub fn println(msg: impl AsRef<str>) {
+    suspend(|| {
+        let mut stdout = stdout().lock();
+        let _ = writeln!(stdout, "{}", msg.as_ref()).and_then(|_| stdout.flush());
+    });
 }
 
-fn nrows() -> u16 {
-    let nrows = NROWS.load(Ordering::Relaxed);
-
-    if nrows != 0 {
-        min(nrows, terminal_size().1)
-    } else {
-        terminal_size().1
+/// How many extra rows a full line drawn at `old_ncols` takes after the terminal
+/// is resized to `new_ncols`, assuming the terminal rewraps lines.
+fn wrapped_rows(old_ncols: u16, new_ncols: u16) -> u16 {
+    match new_ncols {
+        0 => 0,
+        n => (old_ncols.saturating_sub(1)) / n,
     }
 }
 
-fn next_free_pos() -> ID {
-    let mut positions = positions().lock().unwrap();
-    let next_id = NEXTID.fetch_add(1, Ordering::Relaxed);
-    let next_pos = positions.values().max().map(|n| n + 1).unwrap_or(0);
-    positions.insert(next_id, next_pos);
-
-    next_id
-}
-
-fn reposition(id: ID) {
-    let mut positions = positions().lock().unwrap();
-
-    let closed_pos = *positions.get(&id).unwrap();
-
-    positions.remove(&id);
-
-    // Move upwards all the bars below the closed bar
-    positions.iter_mut().for_each(|(_, pos)| {
-        if *pos > closed_pos {
-            *pos -= 1;
-        }
-    });
-}
-
 #[cfg(test)]
 mod tests {
-    use std::time::Instant;
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::time::{Duration, Instant};
+
+    use crossterm::style::Stylize;
 
-    use crate::AvanceBar;
+    use crate::estimator::RateEstimator;
+    use crate::testing::Capture;
+    use crate::{
+        Aggregate, AvanceBar, Color, Colors, DrawTarget, MultiAvance, Spinner, Style, UnitScale,
+    };
 
     #[test]
     fn performance() {
@@ -638,4 +2224,639 @@ mod tests {
         let pb = AvanceBar::new(n);
         for _ in pb.with_iter(0..n) {}
     }
+
+    #[test]
+    fn contention() {
+        let (nthreads, n) = (64, 100_000);
+
+        // Run `n` updates in each of the threads
+        let bench = |name: &str, pb: AvanceBar| {
+            let start = Instant::now();
+            std::thread::scope(|t| {
+                for _ in 0..nthreads {
+                    t.spawn(|| (0..n).for_each(|_| pb.inc()));
+                }
+            });
+            let du = Instant::now().duration_since(start).as_secs_f64();
+            println!("{}: {:.2} it/s", name, (nthreads * n) as f64 / du);
+
+            pb.close();
+            assert_eq!(pb.snapshot().n, nthreads * n);
+        };
+
+        let sink = || DrawTarget::writer(std::io::sink());
+        let multi = MultiAvance::new().with_draw_target(sink());
+        bench("drawn by updating threads", multi.bar(nthreads * n));
+
+        let multi = MultiAvance::new()
+            .with_draw_target(sink())
+            .with_render_thread(Duration::from_millis(50));
+        bench("drawn by a render thread", multi.bar(nthreads * n));
+    }
+
+    #[test]
+    fn template() {
+        let pb = AvanceBar::new(100)
+            .with_desc("task")
+            .with_template("{desc:>6}|{bar:4}|{pos}/{len} {percent}%")
+            .unwrap();
+        pb.update(50);
+
+        let line = pb.state.lock().unwrap().to_string();
+        assert_eq!(line, "  task|##0 |50/100 50%");
+
+        assert!(pb.set_template("{desc").is_err());
+    }
+
+    #[test]
+    fn log_line() {
+        let pb = AvanceBar::new(1000)
+            .with_draw_target(DrawTarget::Hidden)
+            .with_desc("desc");
+        pb.set_postfix("loss=0.1");
+        pb.update(420);
+
+        // Sample the progress again, though drawn within the interval
+        let mut state = pb.state.lock().unwrap();
+        state.last_sample = None;
+        state.sample();
+        let line = state.log_line();
+        assert!(line.starts_with("desc: 42% 420/1000 [00:00<00:00, "));
+        assert!(line.ends_with("it/s, loss=0.1]"));
+    }
+
+    #[test]
+    fn sample_interval() {
+        /// Counts the samples recorded
+        struct Counting(Arc<Mutex<usize>>);
+
+        impl RateEstimator for Counting {
+            fn record(&mut self, _: u64, _: Duration) {
+                *self.0.lock().unwrap() += 1;
+            }
+
+            fn rate(&self) -> Option<f64> {
+                None
+            }
+
+            fn reset(&mut self) {}
+        }
+
+        let samples = Arc::new(Mutex::new(0));
+        let pb = AvanceBar::new(1000)
+            .with_draw_target(DrawTarget::Hidden)
+            .with_estimator(Counting(samples.clone()));
+
+        // Redrawing the bar doesn't feed the estimator more than once per interval
+        let start = Instant::now();
+        for i in 0..1000 {
+            pb.set_postfix(i.to_string());
+        }
+        let intervals = start.elapsed().as_millis() as usize / 100 + 1;
+        assert!(*samples.lock().unwrap() <= intervals);
+    }
+
+    #[test]
+    fn log_step() {
+        assert!(!super::log_step_due(10, 1000, 0, 99));
+        assert!(super::log_step_due(10, 1000, 99, 100));
+        assert!(!super::log_step_due(10, 1000, 100, 199));
+        // Large totals don't overflow
+        assert!(!super::log_step_due(10, u64::MAX, 0, u64::MAX / 20));
+        assert!(super::log_step_due(10, u64::MAX, 0, u64::MAX / 2));
+
+        // Neither an empty nor a huge bar panics when logging
+        for total in [0, u64::MAX] {
+            let pb = AvanceBar::new(total)
+                .with_log_step(10)
+                .with_draw_target(DrawTarget::Stdout);
+            pb.update(total / 2);
+            let mut state = pb.state.lock().unwrap();
+            state.last_log = (Duration::ZERO, 0);
+            let _ = state.log();
+        }
+    }
+
+    #[test]
+    fn position() {
+        let pb = AvanceBar::new(100).with_template("{pos}/{len}").unwrap();
+        let render = || pb.state.lock().unwrap().to_string();
+
+        pb.set_position(40);
+        assert_eq!(render(), "40/100");
+        let stats = pb.state.lock().unwrap().stats();
+        // Jumping doesn't count as progress
+        assert_eq!(stats.its, 0.0);
+        assert_eq!(stats.eta(), None);
+
+        pb.set_total(200);
+        pb.inc_total(50);
+        pb.update(10);
+        assert_eq!(render(), "50/250");
+
+        pb.reset();
+        assert_eq!(render(), "0/250");
+        assert!(pb.progress.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn bytes() {
+        let pb = AvanceBar::new(1 << 30)
+            .with_bytes(UnitScale::IEC)
+            .with_template("{pos}/{len}")
+            .unwrap();
+        pb.update(12_897_485);
+        assert_eq!(pb.state.lock().unwrap().to_string(), "12.3MiB/1.00GiB");
+
+        pb.set_unit_scale(UnitScale::SI);
+        assert_eq!(pb.state.lock().unwrap().to_string(), "12.9MB/1.07GB");
+        assert_eq!(
+            pb.state.lock().unwrap().rate(2_400_000.0).to_string(),
+            "2.40MB/s"
+        );
+    }
+
+    #[test]
+    fn unit() {
+        let pb = AvanceBar::new(100).with_unit("file");
+        let state = pb.state.lock().unwrap();
+
+        assert_eq!(state.rate(34.5).to_string(), "34.50file/s");
+        assert_eq!(state.rate(0.02).to_string(), "50.00s/file");
+        assert_eq!(state.count_with_unit(42).to_string(), "42file");
+    }
+
+    #[test]
+    fn unbounded() {
+        let pb = AvanceBar::with_hint(None)
+            .with_spinner(Spinner::Custom(vec!["a".into(), "b".into()]))
+            .with_template("{spinner}|{bar:10}|")
+            .unwrap();
+
+        let line = pb.state.lock().unwrap().to_string();
+        assert!(line == "a|#         |" || line == "b| #        |");
+
+        let render_bounce = |tick| {
+            let mut bar = String::new();
+            super::render_bounce(&mut bar, &Style::ASCII, tick, 16);
+            bar
+        };
+        assert_eq!(render_bounce(0), "##              ");
+        assert_eq!(render_bounce(14), "              ##");
+        assert_eq!(render_bounce(20), "        ##      ");
+    }
+
+    #[test]
+    fn draw_target() {
+        let capture = Capture::default();
+        let pb = AvanceBar::new(100)
+            .with_draw_target(DrawTarget::writer(capture.clone()))
+            .with_desc("captured");
+        assert!(capture.output().contains("captured:   0%|"));
+
+        pb.set_draw_target(DrawTarget::Hidden);
+        let len = capture.output().len();
+        pb.set_desc("hidden");
+        pb.close();
+        assert_eq!(capture.output().len(), len);
+    }
+
+    #[test]
+    fn colors() {
+        if std::env::var_os("NO_COLOR").is_some() {
+            return;
+        }
+
+        let capture = Capture::default();
+        let pb = AvanceBar::new(100)
+            .with_draw_target(DrawTarget::writer(capture.clone()))
+            .with_template("{desc}|{bar:4}|{pos}")
+            .unwrap()
+            .with_desc("task")
+            .with_colors(
+                Colors::new()
+                    .with_filled(Color::Blue)
+                    .with_background(Color::DarkGrey)
+                    .with_desc(Color::Yellow),
+            );
+        pb.update(50);
+
+        let line = pb.state.lock().unwrap().to_string();
+        let expected = format!("{}|{}{}|50", "task".yellow(), "##0".blue(), " ".dark_grey());
+        assert_eq!(line, expected);
+
+        // No colors on non-terminal targets
+        pb.set_draw_target(DrawTarget::Hidden);
+        assert_eq!(pb.state.lock().unwrap().to_string(), "task|##0 |50");
+    }
+
+    #[test]
+    fn finish() {
+        let capture = Capture::default();
+        let pb = AvanceBar::new(100)
+            .with_draw_target(DrawTarget::writer(capture.clone()))
+            .with_template("{pos}/{len} {postfix}")
+            .unwrap();
+        pb.update(30);
+        pb.finish_with_message("done");
+        assert!(capture.output().contains("100/100 done"));
+        assert!(!pb.state.lock().unwrap().failed);
+
+        let pb = AvanceBar::new(100).with_draw_target(DrawTarget::writer(capture.clone()));
+        pb.update(30);
+        pb.abandon();
+        assert!(pb.state.lock().unwrap().failed);
+
+        // A completed bar is erased, unless it's failed
+        let capture = Capture::default();
+        let pb = AvanceBar::new(10)
+            .with_draw_target(DrawTarget::writer(capture.clone()))
+            .with_leave(false);
+        pb.update(10);
+        pb.close();
+        assert!(capture.output().ends_with("\x1b[J"));
+    }
+
+    #[test]
+    fn early_close() {
+        let capture = Capture::default();
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(capture.clone()));
+
+        // A filtered iterator yields fewer items than its size hint
+        let pb = multi.bar(10).with_leave(false);
+        assert_eq!(pb.with_iter((0..10).filter(|i| i % 2 == 0)).count(), 5);
+        pb.close();
+        assert!(!pb.state.lock().unwrap().failed);
+        assert!(capture.output().ends_with("\x1b[J"));
+
+        let pb = multi.bar(10).with_leave(false);
+        for i in pb.with_iter(0..10) {
+            if i == 3 {
+                break;
+            }
+        }
+        pb.close();
+        assert!(!pb.state.lock().unwrap().failed);
+        assert!(capture.output().ends_with("\x1b[J"));
+    }
+
+    #[test]
+    fn panic() {
+        if std::env::var_os("NO_COLOR").is_some() {
+            return;
+        }
+
+        let capture = Capture::default();
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(capture.clone()));
+        let result = std::thread::spawn(move || {
+            let pb = multi
+                .bar(4)
+                .with_template("{bar:4}")
+                .unwrap()
+                .with_colors(Colors::new().with_failed(Color::Red))
+                .with_leave(false);
+            pb.update(2);
+            panic!("failed halfway");
+        })
+        .join();
+
+        assert!(result.is_err());
+        assert!(capture.output().contains(&"##0".red().to_string()));
+    }
+
+    #[test]
+    fn suspend() {
+        let capture = Capture::default();
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(capture.clone()));
+        let pb = multi.bar(100).with_template("{pos}/{len}").unwrap();
+
+        multi.suspend(|| {
+            let len = capture.output().len();
+            std::thread::sleep(Duration::from_millis(150));
+            // Bars aren't drawn when being suspended
+            pb.update(10);
+            assert_eq!(capture.output().len(), len);
+            // Suspending again doesn't deadlock
+            pb.println("nested");
+        });
+        assert!(capture.output().contains("\x1b[Jnested\n"));
+        assert!(capture.output().trim_end().ends_with("10/100"));
+
+        pb.println("hello");
+        assert!(capture.output().contains("\x1b[Jhello\n"));
+    }
+
+    #[test]
+    fn close_while_suspended() {
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(std::io::sink()));
+        let pb = multi.bar(100);
+        std::thread::scope(|s| {
+            multi.suspend(|| {
+                let closing = s.spawn(|| pb.close());
+                // The bar can still be used until resumed, which then closes it
+                std::thread::sleep(Duration::from_millis(50));
+                pb.set_postfix("suspended");
+                pb.println("suspended");
+                assert!(!closing.is_finished());
+            });
+        });
+
+        for _ in 0..200 {
+            let pb = multi.bar(100);
+            std::thread::scope(|s| {
+                s.spawn(|| pb.println("hello"));
+                s.spawn(|| pb.close());
+            });
+        }
+    }
+
+    #[test]
+    fn unchanged_frame() {
+        let capture = Capture::default();
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(capture.clone()));
+        let pb = multi.bar(100).with_template("{pos}/{len}").unwrap();
+        pb.update(10);
+        pb.refresh();
+
+        // The same line isn't written twice
+        let len = capture.output().len();
+        pb.refresh();
+        assert_eq!(capture.output().len(), len);
+
+        pb.update(10);
+        pb.refresh();
+        assert!(capture.output()[len..].contains("20/100"));
+
+        // Lines erased from the screen are redrawn
+        let len = capture.output().len();
+        multi.suspend(|| {});
+        assert!(capture.output()[len..].contains("20/100"));
+    }
+
+    #[test]
+    fn write_frame() {
+        let mut out = String::new();
+        super::write_frame(&mut out, 0, "abc", 80).unwrap();
+        assert_eq!(out, "\x1b[1Gabc");
+
+        out.clear();
+        super::write_frame(&mut out, 2, "abc", 80).unwrap();
+        assert_eq!(out, "\n\nabc\x1b[2A\x1b[81G");
+    }
+
+    #[test]
+    fn nested() {
+        let multi = MultiAvance::new();
+        let parent = multi
+            .bar(0)
+            .with_template("{desc}{pos}/{len}")
+            .unwrap()
+            .with_aggregate(Aggregate::Sum);
+        let a = parent.child(10);
+        let b = parent.child(20);
+        let grandchild = a.child(5).with_template("{pos}").unwrap();
+
+        let positions: Vec<_> = [&parent, &a, &grandchild, &b]
+            .iter()
+            .map(|pb| pb.state.lock().unwrap().try_get_pos())
+            .collect();
+        assert_eq!(positions, [Some(0), Some(1), Some(2), Some(3)]);
+        assert_eq!(grandchild.state.lock().unwrap().to_string(), "    0");
+
+        a.update(4);
+        b.set_position(10);
+        grandchild.update(5);
+        assert_eq!(parent.state.lock().unwrap().to_string(), "14/30");
+
+        let epochs = AvanceBar::new(2).with_aggregate(Aggregate::Count);
+        let batches = epochs.child(3);
+        batches.update(3);
+        batches.close();
+        let batches = epochs.child(3);
+        batches.update(1);
+        batches.abandon();
+        assert_eq!(epochs.progress.n.get(), 1);
+    }
+
+    #[test]
+    fn nested_finish() {
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::Hidden);
+        let parent = multi.bar(0).with_aggregate(Aggregate::Sum);
+        let child = parent.child(10);
+
+        child.update(3);
+        child.finish();
+        assert_eq!(parent.snapshot().n, 10);
+
+        // Filling up a grandchild fills up its ancestors too
+        let child = parent.child(20).with_aggregate(Aggregate::Sum);
+        let grandchild = child.child(5);
+        grandchild.update(1);
+        grandchild.finish();
+        assert_eq!(child.snapshot().n, 5);
+        assert_eq!(parent.snapshot().n, 15);
+    }
+
+    #[test]
+    fn resize() {
+        assert_eq!(super::wrapped_rows(80, 80), 0);
+        assert_eq!(super::wrapped_rows(80, 120), 0);
+        assert_eq!(super::wrapped_rows(80, 40), 1);
+        assert_eq!(super::wrapped_rows(81, 40), 2);
+
+        let capture = Capture::default();
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(capture.clone()));
+        assert_eq!(multi.group.resized(80), None);
+        assert_eq!(multi.group.resized(80), None);
+        assert_eq!(multi.group.resized(40), Some(80));
+    }
+
+    #[test]
+    fn interrupt() {
+        let capture = Capture::default();
+        let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(capture.clone()));
+        let a = multi
+            .bar(10)
+            .with_desc("a")
+            .with_template("{desc}{pos}")
+            .unwrap();
+        let b = multi
+            .bar(10)
+            .with_desc("b")
+            .with_template("{desc}{pos}")
+            .unwrap();
+
+        // Move the cursor below both bars
+        let deadline = || Instant::now() + Duration::from_millis(100);
+        drop(multi.group.interrupt(false, deadline()));
+        assert!(capture.output().ends_with("\n\n"));
+
+        // Bars suspended by another thread aren't waited for
+        multi.suspend(|| {
+            std::thread::scope(|s| {
+                let len = capture.output().len();
+                s.spawn(|| drop(multi.group.interrupt(true, deadline())))
+                    .join()
+                    .unwrap();
+                assert_eq!(&capture.output()[len..], "\n\n");
+            });
+        });
+        assert!(!a.state.lock().unwrap().failed);
+
+        // Leave both bars on the terminal, in their order, even if one is poisoned
+        a.update(3);
+        let poisoned = b.clone();
+        std::thread::spawn(move || {
+            let _state = poisoned.state.lock().unwrap();
+            panic!("poison");
+        })
+        .join()
+        .unwrap_err();
+        drop(multi.group.interrupt(true, deadline()));
+        let output = capture.output();
+        let (a3, b0) = (output.rfind("a3").unwrap(), output.rfind("b0").unwrap());
+        assert!(a3 < b0);
+        assert!(a.state.lock().unwrap().failed);
+        assert!(
+            b.state
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .failed
+        );
+    }
+
+    #[test]
+    fn snapshot() {
+        let pb = AvanceBar::new(100);
+        let snapshot = pb.snapshot();
+        assert_eq!((snapshot.n, snapshot.total), (0, Some(100)));
+        assert_eq!((snapshot.eta, snapshot.percent), (None, Some(0.0)));
+
+        std::thread::sleep(Duration::from_millis(10));
+        pb.update(40);
+        let snapshot = pb.snapshot();
+        assert_eq!(snapshot.percent, Some(40.0));
+        assert!(snapshot.elapsed >= Duration::from_millis(10));
+
+        // The average rate of a closed bar
+        pb.finish();
+        let snapshot = pb.snapshot();
+        assert!(snapshot.rate.unwrap() > 0.0);
+        assert_eq!(snapshot.eta, Some(Duration::ZERO));
+
+        let pb = AvanceBar::with_hint(None);
+        pb.update(7);
+        let snapshot = pb.snapshot();
+        assert_eq!((snapshot.n, snapshot.total), (7, None));
+        assert_eq!((snapshot.eta, snapshot.percent), (None, None));
+    }
+
+    #[test]
+    fn hooks() {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let milestones = Arc::new(Mutex::new(Vec::new()));
+        let summary = Arc::new(Mutex::new(None));
+        let stalls = Arc::new(Mutex::new(0));
+
+        let multi = MultiAvance::new();
+        let pb = multi
+            .bar(100)
+            .with_draw_target(DrawTarget::Hidden)
+            .on_update({
+                let updates = updates.clone();
+                move |snapshot| updates.lock().unwrap().push(snapshot.n)
+            })
+            .on_percent(30, {
+                let milestones = milestones.clone();
+                move |percent, _| milestones.lock().unwrap().push(percent)
+            })
+            .on_finish({
+                let summary = summary.clone();
+                move |snapshot| *summary.lock().unwrap() = Some(snapshot.clone())
+            })
+            .on_stall(Duration::from_millis(50), {
+                let stalls = stalls.clone();
+                move |_| *stalls.lock().unwrap() += 1
+            });
+
+        std::thread::sleep(Duration::from_millis(150));
+        pb.update(65);
+        assert_eq!(*updates.lock().unwrap(), [65]);
+        assert_eq!(*milestones.lock().unwrap(), [30, 60]);
+
+        // Stalls are noticed when redrawn
+        std::thread::sleep(Duration::from_millis(60));
+        multi.group.redraw_all();
+        multi.group.redraw_all();
+        assert_eq!(*stalls.lock().unwrap(), 1);
+
+        // Callbacks can use their own bar
+        let bar = pb.clone();
+        let pb = pb.on_update(move |_| bar.set_postfix("updated"));
+        pb.finish();
+        assert_eq!(*milestones.lock().unwrap(), [30, 60, 90]);
+        assert_eq!(summary.lock().unwrap().as_ref().unwrap().n, 100);
+        assert_eq!(updates.lock().unwrap().last(), Some(&100));
+    }
+
+    #[test]
+    fn hooks_contention() {
+        let milestones = Arc::new(Mutex::new(Vec::new()));
+        let (started, first) = std::sync::mpsc::channel();
+        let multi = MultiAvance::new();
+        let pb = multi
+            .bar(100)
+            .with_draw_target(DrawTarget::Hidden)
+            .on_percent(10, {
+                let milestones = milestones.clone();
+                move |percent, _| {
+                    milestones.lock().unwrap().push(percent);
+                    if percent == 10 {
+                        let _ = started.send(());
+                        std::thread::sleep(Duration::from_millis(300));
+                    }
+                }
+            });
+
+        std::thread::sleep(Duration::from_millis(150));
+        std::thread::scope(|s| {
+            s.spawn(|| pb.update(10));
+
+            // Wait for the first milestone, then race its slow callback once the bar
+            // is due to be redrawn
+            first.recv().unwrap();
+            std::thread::sleep(Duration::from_millis(120));
+            pb.update(30);
+            assert_eq!(*milestones.lock().unwrap(), [10, 20, 30, 40]);
+        });
+    }
+
+    #[test]
+    fn group() {
+        let capture = Capture::default();
+        let multi = MultiAvance::new()
+            .with_draw_target(DrawTarget::writer(capture.clone()))
+            .with_max_progress_bars(1);
+        let other = MultiAvance::new();
+
+        let a = multi.bar(10);
+        let b = multi.bar(10);
+        let c = other.bar(10);
+        let pos = |pb: &AvanceBar| pb.state.lock().unwrap().try_get_pos();
+        assert_eq!((pos(&a), pos(&b), pos(&c)), (Some(0), Some(1), Some(0)));
+
+        multi.println("hello");
+        assert!(capture.output().contains("hello\n"));
+
+        // Bars of the group are cleared before the line, and redrawn after it
+        let len = capture.output().len();
+        b.println("bye");
+        let output = capture.output();
+        let (_, redrawn) = output[len..].split_once("\x1b[Jbye\n").unwrap();
+        assert!(!redrawn.is_empty());
+
+        a.close();
+        assert_eq!(pos(&b), Some(0));
+        assert_eq!(pos(&c), Some(0));
+    }
 }
@@ -0,0 +1,73 @@
+//! A counter which many threads can increase without contention
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+const SHARDS: usize = 16;
+
+// Next shard to assign to a thread
+static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    // The shard which the current thread adds to
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % SHARDS;
+}
+
+/// Keeps every shard in its own cache line
+#[derive(Debug, Default)]
+#[repr(align(128))]
+struct Shard(AtomicU64);
+
+/// A counter split into shards, so that threads increasing it don't write
+/// to the same cache line. Reading the counter sums all shards.
+#[derive(Debug, Default)]
+pub(crate) struct ShardedCounter {
+    shards: [Shard; SHARDS],
+}
+
+impl ShardedCounter {
+    pub(crate) fn add(&self, delta: u64) {
+        let shard = SHARD.with(|shard| *shard);
+        self.shards[shard].0.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> u64 {
+        self.shards.iter().fold(0, |sum, shard| {
+            sum.wrapping_add(shard.0.load(Ordering::Acquire))
+        })
+    }
+
+    /// Set the counter to `n`, and return the old value.
+    ///
+    /// Increments from other threads in the meantime are kept.
+    pub(crate) fn set(&self, n: u64) -> u64 {
+        let old = self.get();
+        self.shards[0]
+            .0
+            .fetch_add(n.wrapping_sub(old), Ordering::AcqRel);
+        old
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::ShardedCounter;
+
+    #[test]
+    fn counter() {
+        let counter = ShardedCounter::default();
+        thread::scope(|s| {
+            for _ in 0..32 {
+                s.spawn(|| (0..1000).for_each(|_| counter.add(1)));
+            }
+        });
+        assert_eq!(counter.get(), 32_000);
+
+        assert_eq!(counter.set(10), 32_000);
+        assert_eq!(counter.get(), 10);
+        counter.add(5);
+        assert_eq!(counter.set(0), 15);
+        assert_eq!(counter.get(), 0);
+    }
+}
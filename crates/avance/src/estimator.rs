@@ -0,0 +1,325 @@
+//! Estimators of the rate of progress, which also decide the ETA
+//!
+//! A progress bar records a sample when it's redrawn, at most once per 100ms, and asks
+//! its estimator for the current rate. Choose an estimator according to the throughput
+//! of your work:
+//! - [`Ema`]: smooth, and reacts quickly to changes
+//! - [`SlidingWindow`]: the average rate over a recent period of time
+//! - [`Average`]: the overall average rate, which is stable for steady work
+//! - [`LinearRegression`]: a least-squares fit over a recent period of time,
+//!   which is robust to bursty throughput
+
+use std::collections::VecDeque;
+use std::fmt::{self, Debug, Formatter};
+use std::time::Duration;
+
+/// Estimate the rate of progress from samples
+///
+/// # Examples
+/// ```
+/// # use avance::{AvanceBar, estimator::Ema};
+/// let pb = AvanceBar::new(1000).with_estimator(Ema::new(0.1));
+/// ```
+pub trait RateEstimator: Send {
+    /// Record a sample: `n` iterations were finished `elapsed` after the beginning.
+    ///
+    /// `n` never decreases between two resets.
+    fn record(&mut self, n: u64, elapsed: Duration);
+
+    /// The estimated number of iterations per second, or None if unknown
+    fn rate(&self) -> Option<f64>;
+
+    /// Forget all samples, as the progress has restarted.
+    fn reset(&mut self);
+}
+
+impl Debug for dyn RateEstimator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateEstimator")
+            .field("rate", &self.rate())
+            .finish()
+    }
+}
+
+/// The overall average rate since the beginning
+#[derive(Debug, Clone, Default)]
+pub struct Average {
+    last: Option<Sample>,
+}
+
+impl RateEstimator for Average {
+    fn record(&mut self, n: u64, elapsed: Duration) {
+        self.last = Some(Sample::new(n, elapsed));
+    }
+
+    fn rate(&self) -> Option<f64> {
+        self.last.as_ref().and_then(|s| ratio(s.n, s.t))
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+    }
+}
+
+/// Exponential moving average of the rate between two samples
+///
+/// A larger `alpha` gives more weight to recent samples.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    alpha: f64,
+    last: Option<Sample>,
+    rate: Option<f64>,
+}
+
+impl Ema {
+    /// Create an estimator with a smoothing factor between 0 and 1.
+    pub fn new(alpha: f64) -> Self {
+        Self {
+            alpha: alpha.clamp(0.0, 1.0),
+            last: None,
+            rate: None,
+        }
+    }
+}
+
+impl Default for Ema {
+    fn default() -> Self {
+        Self::new(0.3)
+    }
+}
+
+impl RateEstimator for Ema {
+    fn record(&mut self, n: u64, elapsed: Duration) {
+        let sample = Sample::new(n, elapsed);
+        let last = self.last.unwrap_or_default();
+
+        if let Some(current) = ratio(sample.n - last.n, sample.t - last.t) {
+            self.rate = Some(match self.rate {
+                Some(rate) => self.alpha * current + (1.0 - self.alpha) * rate,
+                None => current,
+            });
+            self.last = Some(sample);
+        }
+    }
+
+    fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.rate = None;
+    }
+}
+
+/// The average rate over a recent period of time
+#[derive(Debug, Clone)]
+pub struct SlidingWindow {
+    window: Duration,
+    samples: VecDeque<Sample>,
+}
+
+impl SlidingWindow {
+    /// Create an estimator which only considers samples within `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl Default for SlidingWindow {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(10))
+    }
+}
+
+impl RateEstimator for SlidingWindow {
+    fn record(&mut self, n: u64, elapsed: Duration) {
+        self.samples.push_back(Sample::new(n, elapsed));
+        expire(&mut self.samples, self.window);
+    }
+
+    fn rate(&self) -> Option<f64> {
+        let first = self.samples.front()?;
+        let last = self.samples.back()?;
+        ratio(last.n - first.n, last.t - first.t)
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// The slope of a least-squares line fitted over a recent period of time
+#[derive(Debug, Clone)]
+pub struct LinearRegression {
+    window: Duration,
+    samples: VecDeque<Sample>,
+}
+
+impl LinearRegression {
+    /// Create an estimator which only considers samples within `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+}
+
+impl Default for LinearRegression {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(30))
+    }
+}
+
+impl RateEstimator for LinearRegression {
+    fn record(&mut self, n: u64, elapsed: Duration) {
+        self.samples.push_back(Sample::new(n, elapsed));
+        expire(&mut self.samples, self.window);
+    }
+
+    fn rate(&self) -> Option<f64> {
+        if self.samples.len() < 2 {
+            return None;
+        }
+
+        // Center the samples to keep the precision
+        let len = self.samples.len() as f64;
+        let mean_t = self.samples.iter().map(|s| s.t).sum::<f64>() / len;
+        let mean_n = self.samples.iter().map(|s| s.n).sum::<f64>() / len;
+
+        let (cov, var) = self.samples.iter().fold((0.0, 0.0), |(cov, var), s| {
+            let dt = s.t - mean_t;
+            (cov + dt * (s.n - mean_n), var + dt * dt)
+        });
+
+        ratio(cov, var)
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// The default estimator, which mixes the overall average rate with
+/// the rate since the last sample.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Smoothed {
+    prev: Sample,
+    last: Sample,
+}
+
+impl RateEstimator for Smoothed {
+    fn record(&mut self, n: u64, elapsed: Duration) {
+        self.prev = self.last;
+        self.last = Sample::new(n, elapsed);
+    }
+
+    fn rate(&self) -> Option<f64> {
+        let factor = 0.7;
+        let average = ratio(self.last.n, self.last.t)?;
+
+        match ratio(self.last.n - self.prev.n, self.last.t - self.prev.t) {
+            Some(recent) if recent > 0.0 => Some(average * factor + recent * (1.0 - factor)),
+            _ => Some(average),
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Sample {
+    n: f64,
+    /// Seconds since the beginning
+    t: f64,
+}
+
+impl Sample {
+    fn new(n: u64, elapsed: Duration) -> Self {
+        Self {
+            n: n as f64,
+            t: elapsed.as_secs_f64(),
+        }
+    }
+}
+
+fn ratio(a: f64, b: f64) -> Option<f64> {
+    match a / b {
+        r if r.is_finite() => Some(r),
+        _ => None,
+    }
+}
+
+/// Drop samples which are older than `window`, but keep at least two samples.
+fn expire(samples: &mut VecDeque<Sample>, window: Duration) {
+    let latest = samples.back().map_or(0.0, |s| s.t);
+    while samples.len() > 2 && latest - samples[0].t > window.as_secs_f64() {
+        samples.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(estimator: &mut impl RateEstimator, samples: &[(u64, u64)]) -> Option<f64> {
+        for &(n, millis) in samples {
+            estimator.record(n, Duration::from_millis(millis));
+        }
+        estimator.rate()
+    }
+
+    // 10 it/s for 2 seconds, then 100 it/s for 1 second
+    const BURST: [(u64, u64); 6] = [
+        (0, 0),
+        (10, 1000),
+        (20, 2000),
+        (45, 2250),
+        (70, 2500),
+        (120, 3000),
+    ];
+
+    #[test]
+    fn average() {
+        assert_eq!(feed(&mut Average::default(), &BURST), Some(40.0));
+    }
+
+    #[test]
+    fn ema() {
+        assert_eq!(feed(&mut Ema::new(1.0), &BURST), Some(100.0));
+        assert_eq!(feed(&mut Ema::new(0.5), &BURST[..3]), Some(10.0));
+
+        let mut ema = Ema::new(0.5);
+        assert_eq!(feed(&mut ema, &BURST[..4]), Some(55.0));
+        ema.reset();
+        assert_eq!(ema.rate(), None);
+    }
+
+    #[test]
+    fn sliding_window() {
+        let mut window = SlidingWindow::new(Duration::from_secs(1));
+        assert_eq!(feed(&mut window, &BURST), Some(100.0));
+    }
+
+    #[test]
+    fn linear_regression() {
+        let mut regression = LinearRegression::new(Duration::from_secs(10));
+        assert_eq!(feed(&mut regression, &BURST[..3]), Some(10.0));
+        assert_eq!(regression.rate().map(f64::round), Some(10.0));
+
+        let mut regression = LinearRegression::new(Duration::from_millis(500));
+        assert_eq!(feed(&mut regression, &BURST).map(f64::round), Some(100.0));
+    }
+
+    #[test]
+    fn smoothed() {
+        let rate = feed(&mut Smoothed::default(), &BURST).unwrap();
+        assert!((rate - (40.0 * 0.7 + 100.0 * 0.3)).abs() < 1e-9);
+    }
+}
@@ -1,46 +1,127 @@
-pub fn format_time(seconds: u64) -> String {
-    let m = seconds / 60 % 60;
-    let s = seconds % 60;
-    match seconds / 3600 {
-        0 => format!("{:02}:{:02}", m, s),
-        h => format!("{:02}:{:02}:{:02}", h, m, s),
+use std::fmt::{Display, Formatter, Result};
+
+use crate::style::UnitScale;
+
+/// A duration in seconds, displayed as `mm:ss`, or `hh:mm:ss` if it's an hour or longer
+pub struct Time(pub u64);
+
+impl Display for Time {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let seconds = self.0;
+        let m = seconds / 60 % 60;
+        let s = seconds % 60;
+        match seconds / 3600 {
+            0 => write!(f, "{:02}:{:02}", m, s),
+            h => write!(f, "{:02}:{:02}:{:02}", h, m, s),
+        }
+    }
+}
+
+/// A number displayed with the prefix of the given scale, keeping three significant digits.
+pub struct Scaled(pub f64, pub UnitScale);
+
+impl Display for Scaled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let Scaled(mut num, scale) = *self;
+        let (divisor, units): (f64, [&str; 8]) = match scale {
+            UnitScale::None => return write!(f, "{:.2}", num),
+            UnitScale::SI => (1000.0, ["", "k", "M", "G", "T", "P", "E", "Z"]),
+            UnitScale::IEC => (1024.0, ["", "Ki", "Mi", "Gi", "Ti", "Pi", "Ei", "Zi"]),
+        };
+
+        for unit in units {
+            if num.abs() < 999.5 {
+                if num.abs() < 99.95 {
+                    if num.abs() < 9.995 {
+                        return write!(f, "{:.2}{}", num, unit);
+                    }
+                    return write!(f, "{:.1}{}", num, unit);
+                }
+                return write!(f, "{:.0}{}", num, unit);
+            }
+            num /= divisor;
+        }
+
+        match scale {
+            UnitScale::IEC => write!(f, "{:.1}Yi", num),
+            _ => write!(f, "{:.1}Y", num),
+        }
     }
 }
 
-pub fn format_sizeof(num: u64) -> String {
-    let mut num = num as f64;
-    for unit in ["", "k", "M", "G", "T", "P", "E", "Z"] {
-        if num < 999.5 {
-            if num < 99.95 {
-                if num < 9.995 {
-                    return format!("{:.2}{}", num, unit);
+/// An optional value, displayed as `?` if it's unknown
+pub struct OrUnknown<T>(pub Option<T>);
+
+impl<T: Display> Display for OrUnknown<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match &self.0 {
+            Some(value) => value.fmt(f),
+            None => f.write_str("?"),
+        }
+    }
+}
+
+/// The number of characters displayed, skipping escape sequences such as colors
+pub fn display_width(s: &str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // A CSI sequence ends with a byte in the range 0x40..=0x7E
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
                 }
-                return format!("{:.1}{}", num, unit);
             }
-            return format!("{:.0}{}", num, unit);
+        } else {
+            width += 1;
         }
-        num /= 1000.0;
     }
 
-    format!("{:.1}Y", num)
+    width
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{Scaled, Time};
+    use crate::style::UnitScale;
+
     #[test]
     fn format_time() {
-        assert_eq!(super::format_time(45), "00:45");
-        assert_eq!(super::format_time(30 * 60), "30:00");
-        assert_eq!(super::format_time(12 * 60 * 60), "12:00:00");
+        let format_time = |seconds| Time(seconds).to_string();
+        assert_eq!(format_time(45), "00:45");
+        assert_eq!(format_time(30 * 60), "30:00");
+        assert_eq!(format_time(12 * 60 * 60), "12:00:00");
     }
 
     #[test]
     fn format_sizeof() {
-        assert_eq!(super::format_sizeof(10), "10.0");
-        assert_eq!(super::format_sizeof(1_234), "1.23k");
-        assert_eq!(super::format_sizeof(12_345), "12.3k");
-        assert_eq!(super::format_sizeof(1_234_000), "1.23M");
-        assert_eq!(super::format_sizeof(999_000_000), "999M");
-        assert_eq!(super::format_sizeof(999_999_000), "1.00G");
+        let format_sizeof = |num: u64| Scaled(num as f64, UnitScale::SI).to_string();
+        assert_eq!(format_sizeof(10), "10.0");
+        assert_eq!(format_sizeof(1_234), "1.23k");
+        assert_eq!(format_sizeof(12_345), "12.3k");
+        assert_eq!(format_sizeof(1_234_000), "1.23M");
+        assert_eq!(format_sizeof(999_000_000), "999M");
+        assert_eq!(format_sizeof(999_999_000), "1.00G");
+    }
+
+    #[test]
+    fn format_scaled() {
+        let format_scaled = |num, scale| Scaled(num, scale).to_string();
+
+        assert_eq!(format_scaled(12.345, UnitScale::None), "12.35");
+        assert_eq!(format_scaled(1_000.0, UnitScale::IEC), "0.98Ki");
+        assert_eq!(format_scaled(1_536.0, UnitScale::IEC), "1.50Ki");
+        assert_eq!(format_scaled(12_897_484.8, UnitScale::IEC), "12.3Mi");
+        assert_eq!(format_scaled(2_516_582.4, UnitScale::SI), "2.52M");
+    }
+
+    #[test]
+    fn display_width() {
+        assert_eq!(super::display_width("abc"), 3);
+        assert_eq!(super::display_width("\x1b[38;5;9mabc\x1b[39m d"), 5);
     }
 }
@@ -0,0 +1,604 @@
+//! Groups of progress bars which share a screen
+//!
+//! Every progress bar belongs to a group, which decides where the bar is drawn.
+//! Bars created by [`AvanceBar::new`] belong to a global group, while a [`MultiAvance`]
+//! owns the positions, the row limit and the draw target of its own bars.
+
+use std::cell::Cell;
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::io::{Result, Write};
+use std::ops::Deref;
+use std::sync::{
+    atomic::{AtomicU16, AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError,
+    Weak,
+};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(has_std_once_cell = "false")]
+use once_cell::sync::OnceCell as OnceLock;
+#[cfg(has_std_once_cell = "true")]
+use std::sync::OnceLock;
+
+use crate::bar::State;
+use crate::target::terminal_size;
+use crate::{AvanceBar, DrawTarget};
+
+/// A group of progress bars, which are drawn together to the same target
+///
+/// Bars of different groups don't share positions, so libraries (or tests) can
+/// show their progress bars without interfering with each other.
+///
+/// # Examples
+/// ```
+/// # use avance::{DrawTarget, MultiAvance};
+/// let multi = MultiAvance::new()
+///     .with_draw_target(DrawTarget::Stdout)
+///     .with_max_progress_bars(4);
+///
+/// let download = multi.bar(100).with_desc("download");
+/// let extract = multi.bar(100).with_desc("extract");
+/// multi.println("started");
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiAvance {
+    pub(crate) group: Arc<Group>,
+}
+
+impl MultiAvance {
+    /// Create an empty group, which draws to the standard error
+    pub fn new() -> Self {
+        Self {
+            group: Group::new(),
+        }
+    }
+
+    /// Builder-like function for a group with a draw target
+    /// (default: [`DrawTarget::Stderr`]).
+    ///
+    /// A progress bar in the group draws to the target of the group, unless it has its
+    /// own target (see [`AvanceBar::with_draw_target`]). The target should be set before
+    /// creating any progress bars.
+    pub fn with_draw_target(self, target: DrawTarget) -> Self {
+        *self.group.target.lock().unwrap() = target;
+        self
+    }
+
+    /// Builder-like function for a group showing at most `nbars` progress bars,
+    /// see [`set_max_progress_bars`](Self::set_max_progress_bars).
+    pub fn with_max_progress_bars(self, nbars: u16) -> Self {
+        self.set_max_progress_bars(nbars);
+        self
+    }
+
+    /// Builder-like function for a group whose progress bars are drawn by a dedicated
+    /// thread every `interval`, see [`enable_render_thread`](crate::enable_render_thread).
+    ///
+    /// Only affects progress bars created afterwards.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::MultiAvance;
+    /// # use std::time::Duration;
+    /// let multi = MultiAvance::new().with_render_thread(Duration::from_millis(100));
+    /// let pb = multi.bar(1000);
+    /// // Only adds to a counter
+    /// pb.inc();
+    /// ```
+    pub fn with_render_thread(self, interval: Duration) -> Self {
+        self.group.render(interval);
+        self
+    }
+
+    /// Set how many on-going progress bars of the group can be shown on the screen.
+    ///
+    /// If specified, hides bars outside this limit. If unspecified, adjusts to
+    /// the terminal height.
+    pub fn set_max_progress_bars(&self, nbars: u16) {
+        self.group.set_max_progress_bars(nbars);
+    }
+
+    /// Create a progress bar in the group
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::MultiAvance;
+    /// let multi = MultiAvance::new();
+    /// let pb = multi.bar(1000).with_desc("task");
+    /// ```
+    pub fn bar(&self, total: u64) -> AvanceBar {
+        AvanceBar::in_group(Some(total), Arc::clone(&self.group))
+    }
+
+    /// Clear all progress bars of the group, run `f`, and redraw the bars below its output.
+    ///
+    /// See [`suspend`](crate::suspend)
+    pub fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        self.group.suspend(f)
+    }
+
+    /// Print a line to the draw target of the group, above all its progress bars.
+    pub fn println(&self, msg: impl AsRef<str>) {
+        self.suspend(|| {
+            let _ = self.group.with_writer(|target| {
+                writeln!(target, "{}", msg.as_ref())?;
+                target.flush()
+            });
+        });
+    }
+}
+
+impl Default for MultiAvance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) type AtomicState = Arc<Mutex<State>>;
+pub(crate) type ID = u64;
+pub(crate) type Pos = u16;
+
+/// The book-keeping of a group of progress bars
+#[derive(Debug)]
+pub(crate) struct Group {
+    /// All progress bars which are alive
+    bars: Mutex<HashMap<ID, Weak<Mutex<State>>>>,
+    /// The positions of all bars which are shown
+    positions: Mutex<HashMap<ID, Pos>>,
+    /// The parents of all nested bars
+    parents: Mutex<HashMap<ID, ID>>,
+    /// How many rows are progress bars allowed to use. If unspecified,
+    /// use the terminal height.
+    nrows: AtomicU16,
+    target: Mutex<DrawTarget>,
+    /// The terminal width when bars were last drawn, or zero if never drawn
+    ncols: AtomicU16,
+    /// Interval (in nanoseconds) between two redraws of the render thread.
+    /// Zero means bars are drawn by the threads updating them.
+    render_interval: AtomicU64,
+    /// Held for reading when drawing bars, and for writing when bars are suspended
+    screen: RwLock<()>,
+    /// How many times bars were erased from the screen
+    erasures: AtomicU64,
+}
+
+// Next unused ID
+static NEXTID: AtomicU64 = AtomicU64::new(0);
+// All groups which are alive
+static GROUPS: Mutex<Vec<Weak<Group>>> = Mutex::new(Vec::new());
+// The group of progress bars created without a MultiAvance
+#[cfg_attr(has_std_once_cell = "true", allow(clippy::incompatible_msrv))]
+static GLOBAL: OnceLock<Arc<Group>> = OnceLock::new();
+
+thread_local! {
+    // Whether the current thread is suspending bars
+    static SUSPENDING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) fn next_id() -> ID {
+    NEXTID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// All groups which are alive
+fn groups() -> Vec<Arc<Group>> {
+    let mut groups = GROUPS.lock().unwrap();
+    groups.retain(|group| group.strong_count() > 0);
+    groups.iter().filter_map(Weak::upgrade).collect()
+}
+
+/// Redraw the progress bars of all groups, regardless of the update interval.
+pub(crate) fn redraw_all() {
+    for group in groups() {
+        group.redraw_all();
+    }
+}
+
+/// Clear the progress bars of all groups, and run `f` before redrawing them.
+#[cfg_attr(not(any(feature = "log", feature = "tracing")), allow(dead_code))]
+pub(crate) fn suspend_all<F: FnOnce() -> R, R>(f: F) -> R {
+    suspend_groups(&groups(), f)
+}
+
+/// Clear the progress bars of the given groups, and run `f` while no bar
+/// of them can be drawn.
+fn suspend_groups<G, F, R>(groups: &[G], f: F) -> R
+where
+    G: Deref<Target = Group>,
+    F: FnOnce() -> R,
+{
+    // Suspending bars again in `f` is a no-op
+    if SUSPENDING.with(Cell::get) {
+        return f();
+    }
+
+    let output = {
+        let _screens: Vec<_> = groups
+            .iter()
+            .map(|group| group.screen.write().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        let _suspending = Suspending::new();
+
+        groups.iter().for_each(|group| group.clear_all());
+        f()
+    };
+
+    groups.iter().for_each(|group| group.redraw_all());
+    output
+}
+
+/// Move the cursor below the progress bars of all groups, and run `f` while
+/// no bar can be drawn. Bars are abandoned first if `abandon` is true.
+#[cfg_attr(not(all(unix, feature = "signal")), allow(dead_code))]
+pub(crate) fn interrupt_all<F: FnOnce() -> R, R>(abandon: bool, f: F) -> R {
+    let deadline = Instant::now() + INTERRUPT_TIMEOUT;
+    let groups = groups();
+    let _screens: Vec<_> = groups
+        .iter()
+        .map(|group| group.interrupt(abandon, deadline))
+        .collect();
+    f()
+}
+
+/// How long an interrupt waits for bars which other threads are using
+const INTERRUPT_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Retry `f` until it succeeds, or until the deadline has passed
+fn retry_until<T>(deadline: Instant, mut f: impl FnMut() -> Option<T>) -> Option<T> {
+    loop {
+        if let Some(output) = f() {
+            return Some(output);
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// Lock `mutex` if no other thread holds it, even if it's poisoned
+fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+impl Group {
+    fn new() -> Arc<Self> {
+        let group = Arc::new(Self {
+            bars: Mutex::new(HashMap::new()),
+            positions: Mutex::new(HashMap::new()),
+            parents: Mutex::new(HashMap::new()),
+            nrows: AtomicU16::new(0),
+            target: Mutex::new(DrawTarget::default()),
+            ncols: AtomicU16::new(0),
+            render_interval: AtomicU64::new(0),
+            screen: RwLock::new(()),
+            erasures: AtomicU64::new(0),
+        });
+        GROUPS.lock().unwrap().push(Arc::downgrade(&group));
+
+        #[cfg(all(unix, feature = "signal"))]
+        crate::signal::listen_resize();
+
+        group
+    }
+
+    /// The group of progress bars created without a MultiAvance
+    #[cfg_attr(has_std_once_cell = "true", allow(clippy::incompatible_msrv))]
+    pub(crate) fn global() -> &'static Arc<Self> {
+        GLOBAL.get_or_init(Self::new)
+    }
+
+    pub(crate) fn set_max_progress_bars(&self, nbars: u16) {
+        let nrows = max(nbars + 1, 2);
+        self.nrows.store(nrows, Ordering::Relaxed);
+    }
+
+    /// How many rows bars can use in a terminal of the given height
+    pub(crate) fn nrows_in(&self, height: u16) -> u16 {
+        match self.nrows.load(Ordering::Relaxed) {
+            0 => height,
+            nrows => min(nrows, height),
+        }
+    }
+
+    /// Record the terminal width which bars are drawn at. Returns the previous width
+    /// if the terminal has been resized since bars were last drawn.
+    pub(crate) fn resized(&self, ncols: u16) -> Option<u16> {
+        match self.ncols.swap(ncols, Ordering::Relaxed) {
+            old if old == 0 || old == ncols => None,
+            old => Some(old),
+        }
+    }
+
+    /// Draw the progress bars of the group created from now on in a dedicated thread,
+    /// which redraws them every `interval`. Calling it again changes the interval.
+    pub(crate) fn render(self: &Arc<Self>, interval: Duration) {
+        // At least 1 nanosecond, as zero stands for no render thread
+        let nanos = interval.as_nanos().clamp(1, u64::MAX as u128) as u64;
+        if self.render_interval.swap(nanos, Ordering::Relaxed) != 0 {
+            return;
+        }
+
+        let group = Arc::downgrade(self);
+        thread::Builder::new()
+            .name("avance-render".into())
+            .spawn(move || loop {
+                let interval = match group.upgrade() {
+                    Some(group) => group.render_interval.load(Ordering::Relaxed),
+                    None => return,
+                };
+                thread::sleep(Duration::from_nanos(interval));
+
+                match group.upgrade() {
+                    Some(group) => group.redraw_all(),
+                    None => return,
+                }
+            })
+            .expect("failed to spawn the render thread");
+    }
+
+    pub(crate) fn is_rendered(&self) -> bool {
+        self.render_interval.load(Ordering::Relaxed) != 0
+    }
+
+    pub(crate) fn is_terminal(&self) -> bool {
+        self.target.lock().unwrap().is_terminal()
+    }
+
+    /// The size of the terminal which bars are drawn to, in columns and rows
+    pub(crate) fn terminal_size(&self) -> (u16, u16) {
+        let fixed = self.target.lock().unwrap().fixed_size();
+        fixed.unwrap_or_else(terminal_size)
+    }
+
+    pub(crate) fn is_hidden(&self) -> bool {
+        self.target.lock().unwrap().is_hidden()
+    }
+
+    /// Run `f` with exclusive access to the draw target of the group
+    pub(crate) fn with_writer<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<()>,
+    {
+        self.target.lock().unwrap().with_writer(f)
+    }
+
+    pub(crate) fn register(&self, id: ID, state: &AtomicState) {
+        self.bars.lock().unwrap().insert(id, Arc::downgrade(state));
+    }
+
+    pub(crate) fn unregister(&self, id: ID) {
+        self.bars.lock().unwrap().remove(&id);
+        self.parents.lock().unwrap().remove(&id);
+    }
+
+    pub(crate) fn position(&self, id: ID) -> Option<Pos> {
+        self.positions.lock().unwrap().get(&id).copied()
+    }
+
+    // Whether all progress bars are closed or not
+    pub(crate) fn is_finished(&self) -> bool {
+        self.positions.lock().unwrap().is_empty()
+    }
+
+    // Place a nested bar right below its parent and the other descendants of its parent,
+    // or below all other bars if it's not nested.
+    pub(crate) fn occupy(&self, id: ID) {
+        let mut positions = self.positions.lock().unwrap();
+        let parents = self.parents.lock().unwrap();
+
+        let is_descendant = |mut bar: ID, ancestor: ID| {
+            while let Some(&parent) = parents.get(&bar) {
+                if parent == ancestor {
+                    return true;
+                }
+                bar = parent;
+            }
+            false
+        };
+        let family = parents.get(&id).and_then(|&parent| {
+            positions
+                .iter()
+                .filter(|&(&bar, _)| bar == parent || is_descendant(bar, parent))
+                .map(|(_, &pos)| pos)
+                .max()
+        });
+
+        let next_pos = match family {
+            Some(last) => {
+                // Move downwards all the bars below the family
+                positions.values_mut().for_each(|pos| {
+                    if *pos > last {
+                        *pos += 1;
+                    }
+                });
+                last + 1
+            }
+            None => positions.values().max().map(|n| n + 1).unwrap_or(0),
+        };
+        positions.insert(id, next_pos);
+    }
+
+    // Move a bar under its parent
+    pub(crate) fn nest(&self, id: ID, parent: ID) {
+        self.parents.lock().unwrap().insert(id, parent);
+        if self.position(id).is_some() {
+            self.reposition(id);
+            self.occupy(id);
+        }
+    }
+
+    pub(crate) fn reposition(&self, id: ID) {
+        let mut positions = self.positions.lock().unwrap();
+
+        let closed_pos = *positions.get(&id).unwrap();
+
+        positions.remove(&id);
+
+        // Move upwards all the bars below the closed bar
+        positions.iter_mut().for_each(|(_, pos)| {
+            if *pos > closed_pos {
+                *pos -= 1;
+            }
+        });
+    }
+
+    /// All progress bars which are alive
+    fn states(&self) -> Vec<AtomicState> {
+        // Don't hold the lock when drawing, as dropping the last reference
+        // to a bar would unregister it.
+        self.bars
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    /// Redraw all progress bars which are alive, regardless of the update interval.
+    pub(crate) fn redraw_all(&self) {
+        for state in self.states() {
+            let hooks = {
+                let mut state = state.lock().unwrap();
+                state.redraw();
+                state.progress_hooks()
+            };
+
+            // Keep checking stalled bars
+            if let Some((hooks, snapshot)) = hooks {
+                hooks.progress(&snapshot);
+            }
+        }
+    }
+
+    /// Redraw all progress bars except the given one. Bars locked by other
+    /// threads are skipped, as they're being drawn anyway.
+    pub(crate) fn redraw_others(&self, id: ID) {
+        for state in self.states() {
+            if let Ok(mut state) = state.try_lock() {
+                if state.id() != id {
+                    state.redraw();
+                }
+            }
+        }
+    }
+
+    /// Clear all progress bars, and move the cursor to the beginning of the first line.
+    /// Bars locked by other threads are skipped, which are cleared along with the others
+    /// if they share the same terminal.
+    fn clear_all(&self) {
+        for state in self.states() {
+            if let Ok(mut state) = state.try_lock() {
+                let _ = state.clear_screen();
+            }
+        }
+    }
+
+    /// Move the cursor below all progress bars of the group, abandoning them first if
+    /// `abandon` is true. Bars can't be drawn until the returned guard is dropped.
+    ///
+    /// Bars suspended or locked by other threads, which may be running user code such
+    /// as a prompt, are only waited for until the deadline. The cursor is moved anyway
+    /// then, but such bars aren't abandoned.
+    pub(crate) fn interrupt(
+        &self,
+        abandon: bool,
+        deadline: Instant,
+    ) -> Option<RwLockWriteGuard<'_, ()>> {
+        let screen = retry_until(deadline, || match self.screen.try_write() {
+            Ok(screen) => Some(screen),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        });
+
+        if abandon && screen.is_some() {
+            // Close from the top, so that bars are left in their order
+            let mut states: Vec<_> = self
+                .states()
+                .into_iter()
+                .filter_map(|state| {
+                    let id = retry_until(deadline, || try_lock(&state))?.id();
+                    self.position(id).map(|pos| (pos, state))
+                })
+                .collect();
+            states.sort_by_key(|&(pos, _)| pos);
+
+            for (_, state) in states {
+                if let Some(mut state) = retry_until(deadline, || try_lock(&state)) {
+                    let _ = state.abandon();
+                }
+            }
+        }
+
+        let mut target = self.target.lock().unwrap_or_else(PoisonError::into_inner);
+        if target.is_terminal() {
+            let nbars = self
+                .positions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .len();
+            let height = target.fixed_size().unwrap_or_else(terminal_size).1;
+            let rows = min(nbars, self.nrows_in(height) as usize);
+            let _ = target.with_writer(|target| {
+                target.write_all("\n".repeat(rows).as_bytes())?;
+                target.flush()
+            });
+            self.mark_erased();
+        }
+
+        screen
+    }
+
+    pub(crate) fn suspend<F: FnOnce() -> R, R>(&self, f: F) -> R {
+        suspend_groups(&[self], f)
+    }
+
+    /// Note that bars were erased from the screen, after the erasing was written.
+    /// Bars redraw their lines then, even if nothing has changed.
+    pub(crate) fn mark_erased(&self) {
+        self.erasures.fetch_add(1, Ordering::Release);
+    }
+
+    /// How many times bars were erased from the screen
+    pub(crate) fn erasures(&self) -> u64 {
+        self.erasures.load(Ordering::Acquire)
+    }
+
+    /// Hold the screen for drawing, or None if bars are suspended.
+    pub(crate) fn try_hold_screen(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        match self.screen.try_read() {
+            Ok(screen) => Some(screen),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Wait until bars are resumed, unless they're suspended by the current thread.
+    pub(crate) fn hold_screen(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        match SUSPENDING.with(Cell::get) {
+            true => None,
+            false => Some(self.screen.read().unwrap_or_else(PoisonError::into_inner)),
+        }
+    }
+}
+
+/// Marks the current thread as suspending bars, until dropped
+struct Suspending;
+
+impl Suspending {
+    fn new() -> Self {
+        SUSPENDING.with(|s| s.set(true));
+        Suspending
+    }
+}
+
+impl Drop for Suspending {
+    fn drop(&mut self) {
+        SUSPENDING.with(|s| s.set(false));
+    }
+}
@@ -0,0 +1,173 @@
+//! Callbacks fired when a progress bar advances, stalls or closes
+//!
+//! Callbacks are never fired with the state of the progress bar locked, so they're free
+//! to use the progress bar. Callbacks fired by themselves again on the same thread (for
+//! example, an update callback which updates its own bar) are skipped instead of deadlocking.
+//! Otherwise, threads wait for each other, so no update or milestone is missed.
+
+use std::cell::RefCell;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use crate::ProgressSnapshot;
+
+thread_local! {
+    // Addresses of the hooks which the current thread is firing
+    static FIRING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+type Callback = Box<dyn FnMut(&ProgressSnapshot) + Send>;
+type MilestoneCallback = Box<dyn FnMut(u8, &ProgressSnapshot) + Send>;
+
+/// All callbacks registered on a progress bar
+#[derive(Default)]
+pub(crate) struct Hooks {
+    update: Mutex<Vec<Callback>>,
+    percent: Mutex<Vec<Milestone>>,
+    finish: Mutex<Vec<Callback>>,
+    stall: Mutex<Stall>,
+}
+
+/// Fired every `step` percent
+struct Milestone {
+    step: u8,
+    /// The last percentage which has been reached
+    last: u8,
+    callback: MilestoneCallback,
+}
+
+/// Fired once if there's no progress for some time, until the progress changes
+#[derive(Default)]
+struct Stall {
+    /// Number of iterations, and elapsed time of the last change
+    last_change: (u64, Duration),
+    callbacks: Vec<(Duration, bool, Callback)>,
+}
+
+impl Hooks {
+    pub(crate) fn on_update(&self, f: Callback) {
+        self.update.lock().unwrap().push(f);
+    }
+
+    pub(crate) fn on_percent(&self, step: u8, f: MilestoneCallback) {
+        self.percent.lock().unwrap().push(Milestone {
+            step: step.clamp(1, 100),
+            last: 0,
+            callback: f,
+        });
+    }
+
+    pub(crate) fn on_finish(&self, f: Callback) {
+        self.finish.lock().unwrap().push(f);
+    }
+
+    pub(crate) fn on_stall(&self, timeout: Duration, f: Callback) {
+        self.stall
+            .lock()
+            .unwrap()
+            .callbacks
+            .push((timeout, false, f));
+    }
+
+    /// Fire callbacks on the progress of a bar, including milestones and stalls.
+    pub(crate) fn progress(&self, snapshot: &ProgressSnapshot) {
+        let _firing = match Firing::enter(self) {
+            Some(firing) => firing,
+            None => return,
+        };
+
+        self.advance(snapshot);
+        lock(&self.stall).check(snapshot);
+    }
+
+    /// Fire callbacks on the closing of a bar
+    pub(crate) fn finish(&self, snapshot: &ProgressSnapshot) {
+        let _firing = match Firing::enter(self) {
+            Some(firing) => firing,
+            None => return,
+        };
+
+        // Report the final progress, which may reach the last milestone
+        self.advance(snapshot);
+        lock(&self.finish).iter_mut().for_each(|f| f(snapshot));
+    }
+
+    fn advance(&self, snapshot: &ProgressSnapshot) {
+        lock(&self.update).iter_mut().for_each(|f| f(snapshot));
+
+        if let Some(percent) = snapshot.percent {
+            let mut milestones = lock(&self.percent);
+            let percent = percent as u8;
+            for milestone in milestones.iter_mut() {
+                let step = milestone.step;
+                let reached = percent / step * step;
+
+                // Milestones are reached again after the progress is reset
+                if reached < milestone.last {
+                    milestone.last = reached;
+                }
+                while milestone.last < reached {
+                    milestone.last += step;
+                    (milestone.callback)(milestone.last, snapshot);
+                }
+            }
+        }
+    }
+}
+
+/// Lock callbacks, even if a callback panicked with them locked
+fn lock<T>(callbacks: &Mutex<T>) -> MutexGuard<'_, T> {
+    callbacks.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Marks hooks as being fired by the current thread, until dropped
+struct Firing(usize);
+
+impl Firing {
+    /// None if the current thread is already firing the hooks
+    fn enter(hooks: &Hooks) -> Option<Self> {
+        let id = hooks as *const Hooks as usize;
+        FIRING.with(|firing| {
+            let mut firing = firing.borrow_mut();
+            if firing.contains(&id) {
+                return None;
+            }
+            firing.push(id);
+            Some(Firing(id))
+        })
+    }
+}
+
+impl Drop for Firing {
+    fn drop(&mut self) {
+        FIRING.with(|firing| firing.borrow_mut().retain(|&id| id != self.0));
+    }
+}
+
+impl Stall {
+    fn check(&mut self, snapshot: &ProgressSnapshot) {
+        let (n, since) = self.last_change;
+        if snapshot.n != n {
+            self.last_change = (snapshot.n, snapshot.elapsed);
+            self.callbacks
+                .iter_mut()
+                .for_each(|(_, fired, _)| *fired = false);
+            return;
+        }
+
+        let stalled = snapshot.elapsed.saturating_sub(since);
+        for (timeout, fired, f) in self.callbacks.iter_mut() {
+            if !*fired && stalled >= *timeout {
+                *fired = true;
+                f(snapshot);
+            }
+        }
+    }
+}
+
+impl Debug for Hooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hooks").finish_non_exhaustive()
+    }
+}
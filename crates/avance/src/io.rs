@@ -0,0 +1,275 @@
+//! Readers and writers that show how many bytes are transferred
+//!
+//! See [`AvanceBar::wrap_read`] and [`AvanceBar::wrap_write`]. With the `tokio` feature,
+//! the wrappers also implement the asynchronous I/O traits of [`tokio`](https://docs.rs/tokio).
+
+use std::io::{BufRead, IoSlice, IoSliceMut, Read, Result, Seek, SeekFrom, Write};
+
+use pin_project_lite::pin_project;
+
+use crate::AvanceBar;
+
+pin_project! {
+    /// A reader wrapper that advances a progress bar by the bytes read
+    #[derive(Debug)]
+    pub struct AvanceRead<R> {
+        #[pin]
+        inner: R,
+        bar: AvanceBar,
+    }
+}
+
+pin_project! {
+    /// A writer wrapper that advances a progress bar by the bytes written
+    #[derive(Debug)]
+    pub struct AvanceWrite<W> {
+        #[pin]
+        inner: W,
+        bar: AvanceBar,
+    }
+}
+
+impl<R> AvanceRead<R> {
+    pub(crate) fn new(inner: R, bar: AvanceBar) -> Self {
+        Self { inner, bar }
+    }
+
+    /// The progress bar advanced by the reader
+    pub fn bar(&self) -> &AvanceBar {
+        &self.bar
+    }
+
+    /// Unwrap the reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<W> AvanceWrite<W> {
+    pub(crate) fn new(inner: W, bar: AvanceBar) -> Self {
+        Self { inner, bar }
+    }
+
+    /// The progress bar advanced by the writer
+    pub fn bar(&self) -> &AvanceBar {
+        &self.bar
+    }
+
+    /// Unwrap the writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for AvanceRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bar.update(n as u64);
+        Ok(n)
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> Result<usize> {
+        let n = self.inner.read_vectored(bufs)?;
+        self.bar.update(n as u64);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for AvanceRead<R> {
+    fn fill_buf(&mut self) -> Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.bar.update(amt as u64);
+    }
+}
+
+/// Seeking moves the progress bar to the new offset
+impl<R: Seek> Seek for AvanceRead<R> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let offset = self.inner.seek(pos)?;
+        self.bar.set_position(offset);
+        Ok(offset)
+    }
+}
+
+impl<W: Write> Write for AvanceWrite<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bar.update(n as u64);
+        Ok(n)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> Result<usize> {
+        let n = self.inner.write_vectored(bufs)?;
+        self.bar.update(n as u64);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Seeking moves the progress bar to the new offset
+impl<W: Seek> Seek for AvanceWrite<W> {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let offset = self.inner.seek(pos)?;
+        self.bar.set_position(offset);
+        Ok(offset)
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_io {
+    use std::io::{Result, SeekFrom};
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+    use super::{AvanceRead, AvanceWrite};
+
+    impl<R: AsyncRead> AsyncRead for AvanceRead<R> {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<Result<()>> {
+            let this = self.project();
+            let filled = buf.filled().len();
+            let poll = this.inner.poll_read(cx, buf);
+            if let Poll::Ready(Ok(())) = poll {
+                this.bar.update((buf.filled().len() - filled) as u64);
+            }
+            poll
+        }
+    }
+
+    impl<R: AsyncBufRead> AsyncBufRead for AvanceRead<R> {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<&[u8]>> {
+            self.project().inner.poll_fill_buf(cx)
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            let this = self.project();
+            this.inner.consume(amt);
+            this.bar.update(amt as u64);
+        }
+    }
+
+    impl<R: AsyncSeek> AsyncSeek for AvanceRead<R> {
+        fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> Result<()> {
+            self.project().inner.start_seek(position)
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<u64>> {
+            let this = self.project();
+            let poll = this.inner.poll_complete(cx);
+            if let Poll::Ready(Ok(offset)) = poll {
+                this.bar.set_position(offset);
+            }
+            poll
+        }
+    }
+
+    impl<W: AsyncWrite> AsyncWrite for AvanceWrite<W> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<Result<usize>> {
+            let this = self.project();
+            let poll = this.inner.poll_write(cx, buf);
+            if let Poll::Ready(Ok(n)) = poll {
+                this.bar.update(n as u64);
+            }
+            poll
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+            self.project().inner.poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+            self.project().inner.poll_shutdown(cx)
+        }
+    }
+
+    impl<W: AsyncSeek> AsyncSeek for AvanceWrite<W> {
+        fn start_seek(self: Pin<&mut Self>, position: SeekFrom) -> Result<()> {
+            self.project().inner.start_seek(position)
+        }
+
+        fn poll_complete(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<u64>> {
+            let this = self.project();
+            let poll = this.inner.poll_complete(cx);
+            if let Poll::Ready(Ok(offset)) = poll {
+                this.bar.set_position(offset);
+            }
+            poll
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{copy, BufRead, Cursor, Read, Seek, SeekFrom};
+
+    use crate::{AvanceBar, DrawTarget};
+
+    fn bar(total: u64) -> AvanceBar {
+        AvanceBar::new(total).with_draw_target(DrawTarget::Hidden)
+    }
+
+    #[test]
+    fn read_write() {
+        let data = vec![7u8; 1000];
+        let pb = bar(1000);
+        let mut reader = pb.wrap_read(Cursor::new(&data));
+
+        let mut head = [0; 100];
+        reader.read_exact(&mut head).unwrap();
+        assert_eq!(pb.snapshot().n, 100);
+
+        reader.fill_buf().unwrap();
+        reader.consume(50);
+        assert_eq!(pb.snapshot().n, 150);
+
+        reader.seek(SeekFrom::Start(500)).unwrap();
+        assert_eq!(pb.snapshot().n, 500);
+
+        let written = bar(500);
+        let mut writer = written.wrap_write(Vec::new());
+        assert_eq!(copy(&mut reader, &mut writer).unwrap(), 500);
+        assert_eq!(pb.snapshot().n, 1000);
+        assert_eq!(written.snapshot().n, 500);
+        assert_eq!(writer.into_inner().len(), 500);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio() {
+        use futures::executor::block_on;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let data = vec![7u8; 1000];
+        let pb = bar(1000);
+        let written = bar(1000);
+
+        block_on(async {
+            let mut reader = pb.wrap_read(&data[..]);
+            let mut writer = written.wrap_write(Vec::new());
+
+            let mut buf = Vec::new();
+            AsyncReadExt::read_to_end(&mut reader, &mut buf)
+                .await
+                .unwrap();
+            writer.write_all(&buf).await.unwrap();
+        });
+        assert_eq!(pb.snapshot().n, 1000);
+        assert_eq!(written.snapshot().n, 1000);
+    }
+}
@@ -88,12 +88,82 @@ impl<Iter: Iterator> AvanceIter<Iter> {
     }
 
     /// Displaying numbers in a human readable format, using SI metric prefix
-    /// (k = 10^3, M = 10^6, etc.)
-    pub fn with_unit_scale(self, unit_scale: bool) -> Self {
+    /// (k = 10^3, M = 10^6, etc.) if `true` is given.
+    ///
+    /// See [AvanceBar::with_unit_scale]
+    pub fn with_unit_scale(self, unit_scale: impl Into<UnitScale>) -> Self {
         self.bar.set_unit_scale(unit_scale);
         self
     }
 
+    /// Set the spinner of a progress bar, which is shown when the length
+    /// of the iterator is unknown.
+    ///
+    /// See [AvanceBar::with_spinner]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use avance::{AvanceIterator, Spinner};
+    /// for _ in (0..).take_while(|&i| i < 1000).avance().with_spinner(Spinner::Line) {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn with_spinner(self, spinner: Spinner) -> Self {
+        self.bar.set_spinner(spinner);
+        self
+    }
+
+    /// Show a bouncing animation when the length of the iterator is unknown.
+    ///
+    /// See [AvanceBar::with_bounce]
+    pub fn with_bounce(self, bounce: bool) -> Self {
+        self.bar.set_bounce(bounce);
+        self
+    }
+
+    /// Set the colors of a progress bar.
+    ///
+    /// See [AvanceBar::with_colors]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use avance::{AvanceIterator, Color, Colors};
+    /// for _ in (0..1000).avance().with_colors(Colors::new().with_filled(Color::Green)) {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn with_colors(self, colors: Colors) -> Self {
+        self.bar.set_colors(colors);
+        self
+    }
+
+    /// Whether to leave the progress bar on the terminal once completed (default: true).
+    ///
+    /// See [AvanceBar::with_leave]
+    pub fn with_leave(self, leave: bool) -> Self {
+        self.bar.set_leave(leave);
+        self
+    }
+
+    /// Set the unit name (default: `it`) of a progress bar.
+    ///
+    /// See [AvanceBar::with_unit]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use avance::AvanceIterator;
+    /// for _ in (0..1000).avance().with_unit("file") {
+    ///     // ...
+    /// }
+    /// ```
+    pub fn with_unit(self, unit: impl Into<Cow<'static, str>>) -> Self {
+        self.bar.set_unit(unit);
+        self
+    }
+
     /// Set a progress bar's width
     ///
     /// See [AvanceBar::with_width]
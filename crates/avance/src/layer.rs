@@ -0,0 +1,298 @@
+//! Integration with [`tracing`](https://docs.rs/tracing), enabled by the `tracing` feature
+//!
+//! [`AvanceLayer`] turns spans into progress bars. A span gets a progress bar if it
+//! declares one of the following fields:
+//! - `avance.total`: the total length of the progress bar. Declare it with
+//!   `tracing::field::Empty` for a bar of unknown length, and record it later.
+//! - `avance.pos`: the absolute position of the progress bar
+//! - `avance.inc`: advance the progress bar by some steps
+//!
+//! Fields can be updated with `Span::record`. An event carrying `avance.inc` or `avance.pos`
+//! advances the progress bar of its closest ancestor span. The progress bar is closed
+//! when its span closes.
+//!
+//! Events are printed above the bars if they're formatted with an [`AvanceWriter`].
+//!
+//! # Examples
+//! ```
+//! use avance::layer::{AvanceLayer, AvanceWriter};
+//! use tracing_subscriber::prelude::*;
+//!
+//! tracing_subscriber::registry()
+//!     .with(AvanceLayer::new())
+//!     .with(tracing_subscriber::fmt::layer().with_writer(AvanceWriter::stderr()))
+//!     .init();
+//!
+//! let span = tracing::info_span!("download", avance.total = 100);
+//! let _enter = span.enter();
+//! for _ in 0..100 {
+//!     tracing::trace!(avance.inc = 1);
+//! }
+//! ```
+
+use std::fmt::{self, Debug, Formatter};
+use std::io::{self, stderr, stdout, Write};
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::group::suspend_all;
+use crate::AvanceBar;
+
+const TOTAL: &str = "avance.total";
+const POS: &str = "avance.pos";
+const INC: &str = "avance.inc";
+
+/// A [`Layer`] which shows a progress bar for each span carrying `avance.*` fields
+///
+/// See the [module-level documentation](self) for details.
+pub struct AvanceLayer {
+    build: Box<dyn Fn(AvanceBar) -> AvanceBar + Send + Sync>,
+}
+
+impl AvanceLayer {
+    /// Create a layer, whose progress bars are described by the names of spans
+    pub fn new() -> Self {
+        Self {
+            build: Box::new(|pb| pb),
+        }
+    }
+
+    /// Builder-like function to configure the progress bar of every span
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::{layer::AvanceLayer, Style};
+    /// let layer = AvanceLayer::new().with_bar(|pb| pb.with_style(Style::Block).with_width(80));
+    /// ```
+    pub fn with_bar<F>(mut self, build: F) -> Self
+    where
+        F: Fn(AvanceBar) -> AvanceBar + Send + Sync + 'static,
+    {
+        self.build = Box::new(build);
+        self
+    }
+}
+
+impl Default for AvanceLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for AvanceLayer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AvanceLayer").finish_non_exhaustive()
+    }
+}
+
+impl<S> Layer<S> for AvanceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let fields = attrs.metadata().fields();
+        if [TOTAL, POS, INC]
+            .iter()
+            .all(|name| fields.field(name).is_none())
+        {
+            return;
+        }
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let mut values = Values::default();
+        attrs.record(&mut values);
+
+        let pb = (self.build)(AvanceBar::with_hint(None).with_desc(attrs.metadata().name()));
+        values.apply(&pb);
+        span.extensions_mut().insert(pb);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(pb) = span.extensions().get::<AvanceBar>() {
+                let mut fields = Values::default();
+                values.record(&mut fields);
+                fields.apply(pb);
+            }
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut values = Values::default();
+        event.record(&mut values);
+        if values.pos.is_none() && values.inc.is_none() {
+            return;
+        }
+
+        // Advance the progress bar of the closest span
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope {
+                if let Some(pb) = span.extensions().get::<AvanceBar>() {
+                    values.apply(pb);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(&id) {
+            if let Some(pb) = span.extensions_mut().remove::<AvanceBar>() {
+                pb.close();
+            }
+        }
+    }
+}
+
+/// Values of `avance.*` fields
+#[derive(Default)]
+struct Values {
+    total: Option<u64>,
+    pos: Option<u64>,
+    inc: Option<u64>,
+}
+
+impl Values {
+    fn apply(&self, pb: &AvanceBar) {
+        if let Some(total) = self.total {
+            pb.set_total(total);
+        }
+        if let Some(pos) = self.pos {
+            pb.set_position(pos);
+        }
+        if let Some(inc) = self.inc {
+            pb.update(inc);
+        }
+    }
+}
+
+impl Visit for Values {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            TOTAL => self.total = Some(value),
+            POS => self.pos = Some(value),
+            INC => self.inc = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if value >= 0 {
+            self.record_u64(field, value as u64);
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn Debug) {}
+}
+
+/// A [`MakeWriter`] which writes above all progress bars
+///
+/// Each event is buffered, and written at once when it's fully formatted.
+#[derive(Debug, Clone, Copy)]
+pub struct AvanceWriter {
+    stdout: bool,
+}
+
+impl AvanceWriter {
+    /// Write to the standard error
+    pub fn stderr() -> Self {
+        Self { stdout: false }
+    }
+
+    /// Write to the standard output
+    pub fn stdout() -> Self {
+        Self { stdout: true }
+    }
+}
+
+impl<'a> MakeWriter<'a> for AvanceWriter {
+    type Writer = SuspendedWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SuspendedWriter {
+            stdout: self.stdout,
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// The writer made by [`AvanceWriter`], which writes the buffered output
+/// above all progress bars when dropped.
+#[derive(Debug)]
+pub struct SuspendedWriter {
+    stdout: bool,
+    buf: Vec<u8>,
+}
+
+impl Write for SuspendedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SuspendedWriter {
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+
+        suspend_all(|| {
+            let _ = match self.stdout {
+                true => stdout().lock().write_all(&self.buf),
+                false => stderr().lock().write_all(&self.buf),
+            };
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Capture;
+    use crate::DrawTarget;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn spans() {
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let layer = AvanceLayer::new().with_bar(move |pb| {
+            pb.with_draw_target(DrawTarget::writer(writer.clone()))
+                .with_template("{desc} {pos}/{len}")
+                .unwrap()
+        });
+        let subscriber = tracing_subscriber::registry().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("task", avance.total = tracing::field::Empty);
+            let _enter = span.enter();
+            span.record("avance.total", 10);
+
+            tracing::info!(avance.inc = 3);
+            tracing::info_span!("inner").in_scope(|| tracing::info!(avance.inc = 2));
+            // Events without avance fields are ignored
+            tracing::info!(n = 100);
+            span.record("avance.total", 20);
+
+            // Spans without avance fields don't have progress bars
+            let _plain = tracing::info_span!("plain", n = 1).entered();
+        });
+
+        let output = capture.output();
+        assert!(output.contains("task 0/?"));
+        assert!(output.contains("task 5/20"));
+        assert!(!output.contains("plain"));
+    }
+}
@@ -55,6 +55,10 @@
 //!   - configuration changes (such as changing its style or width)
 //! - If a progress bar's width is too large, environment width will be used instead.
 //! - A progress bar can be **shared among threads fearlessly**.
+//! - Use [`println`] or [`suspend`] to print text while progress bars are on screen,
+//!   otherwise the text may be overwritten by progress bars.
+//! - If the output is not a terminal (e.g. redirected to a file), a progress bar
+//!   prints a plain line periodically instead (see [`AvanceBar::with_log_interval`]).
 //!
 //! # Iterator
 //!
@@ -74,6 +78,36 @@
 //! }
 //! ```
 //!
+//! # Nested bars
+//!
+//! A progress bar can have children, which are drawn indented below it. The parent
+//! can advance along with its children (see [`Aggregate`]).
+//!
+//! ```
+//! use avance::{Aggregate, AvanceBar};
+//!
+//! let epochs = AvanceBar::new(10).with_aggregate(Aggregate::Count);
+//! for _ in 0..10 {
+//!     for _ in epochs.child(100).with_iter(0..100) {
+//!         // ...
+//!     }
+//! }
+//! ```
+//!
+//! # Groups
+//!
+//! Progress bars created by [`AvanceBar::new`] share the screen with all other bars of
+//! the process. A [`MultiAvance`] owns its own positions, row limit and draw target,
+//! so that a library can show its progress bars without disturbing the others.
+//!
+//! ```
+//! use avance::MultiAvance;
+//!
+//! let multi = MultiAvance::new().with_max_progress_bars(2);
+//! let download = multi.bar(100).with_desc("download");
+//! let extract = multi.bar(100).with_desc("extract");
+//! ```
+//!
 //! # Style
 //!
 //! avance provides a range of pre-definded progress styles (at [`Style`]),
@@ -86,18 +120,123 @@
 //! }
 //! ```
 //!
-//! # TODOs:
-//! - [ ] A monitor for very slow progress bars
-//! - [ ] A convenient template for customizing progress bars
+//! Progress bars of unknown length can show a [`Spinner`] or a bouncing animation.
+//!
+//! ```
+//! # use avance::{AvanceIterator, Spinner};
+//! for _ in (0..).take_while(|&i| i < 1000).avance().with_spinner(Spinner::Dots) {
+//!     // ...
+//! }
+//! ```
+//!
+//! Bars can be colored with [`Colors`], which change on completion or failure.
+//!
+//! ```
+//! # use avance::{AvanceBar, Color, Colors};
+//! let pb = AvanceBar::new(100).with_colors(
+//!     Colors::new()
+//!         .with_filled(Color::Blue)
+//!         .with_done(Color::Green)
+//!         .with_failed(Color::Red),
+//! );
+//! ```
+//!
+//! # Template
+//!
+//! The layout of a progress bar can be customized with a template (see [`template`]).
+//!
+//! ```
+//! # use avance::AvanceBar;
+//! let pb = AvanceBar::new(100)
+//!     .with_template("{desc} {bar:40} {pos}/{len} [{elapsed}<{eta}, {rate}]")
+//!     .unwrap();
+//! ```
+//!
+//! # Monitor
+//!
+//! A progress bar is redrawn only when it advances. For very slow progress bars,
+//! a background monitor keeps the elapsed time and rate moving.
+//!
+//! ```
+//! use avance::enable_monitor;
+//! use std::time::Duration;
+//!
+//! enable_monitor(Duration::from_secs(1));
+//! ```
+//!
+//! For progress bars advanced by many threads at a high rate, [`enable_render_thread`]
+//! draws all progress bars in a single thread, so that advancing a bar only adds to
+//! a counter.
+//!
+//! # Feature flags
+//!
+//! - `futures`: show the progress of a [`Stream`](https://docs.rs/futures/latest/futures/stream/trait.Stream.html)
+//!   with `AvanceStream`, like [`AvanceIterator`] does for iterators
+//! - `log`: route records of the [`log`](https://docs.rs/log) crate above
+//!   progress bars (see the `logger` module)
+//! - `tokio`: implement the asynchronous I/O traits of [`tokio`](https://docs.rs/tokio)
+//!   for [`AvanceRead`] and [`AvanceWrite`]
+//! - `tracing`: show a progress bar for each [`tracing`](https://docs.rs/tracing) span
+//!   carrying `avance.*` fields (see the `layer` module)
+//! - `rayon`: show the progress of a [`rayon`](https://docs.rs/rayon) parallel iterator
+//!   with `ParallelAvanceIterator`
+//! - `serde`: serialize and deserialize [`ProgressSnapshot`]
+//! - `signal`: redraw progress bars as soon as the terminal is resized, and restore
+//!   the terminal on Ctrl-C with [`handle_interrupts`] (Unix only). Without it,
+//!   progress bars adapt to the new size on their next update.
 
 pub mod bar;
+pub(crate) mod counter;
+pub mod estimator;
 pub(crate) mod format;
+pub mod group;
+pub(crate) mod hook;
+pub mod io;
 pub mod iter;
+#[cfg(feature = "tracing")]
+pub mod layer;
+#[cfg(feature = "log")]
+pub mod logger;
+pub mod monitor;
+#[cfg(feature = "rayon")]
+pub mod par_iter;
+#[cfg(all(unix, feature = "signal"))]
+mod signal;
+#[cfg(feature = "futures")]
+pub mod stream;
 pub mod style;
+pub mod target;
+pub mod template;
+#[cfg(test)]
+mod testing;
 
 #[doc(inline)]
-pub use bar::{set_max_progress_bars, AvanceBar};
+pub use bar::{println, set_max_progress_bars, suspend, Aggregate, AvanceBar, ProgressSnapshot};
+#[doc(inline)]
+pub use estimator::RateEstimator;
+#[doc(inline)]
+pub use group::MultiAvance;
+#[doc(inline)]
+pub use io::{AvanceRead, AvanceWrite};
 #[doc(inline)]
 pub use iter::{AvanceBarIter, AvanceIter, AvanceIterator};
+#[cfg(feature = "log")]
+#[doc(inline)]
+pub use logger::AvanceLogger;
+#[doc(inline)]
+pub use monitor::{disable_monitor, enable_monitor, enable_render_thread};
+#[cfg(feature = "rayon")]
+#[doc(inline)]
+pub use par_iter::{AvanceParIter, ParallelAvanceIterator};
+#[cfg(all(unix, feature = "signal"))]
+#[doc(inline)]
+pub use signal::handle_interrupts;
+#[cfg(feature = "futures")]
+#[doc(inline)]
+pub use stream::{AvanceBarStream, AvanceStream, AvanceStreamer};
+#[doc(inline)]
+pub use style::{Color, Colors, Spinner, Style, UnitScale};
+#[doc(inline)]
+pub use target::DrawTarget;
 #[doc(inline)]
-pub use style::Style;
+pub use template::TemplateError;
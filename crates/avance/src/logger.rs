@@ -0,0 +1,132 @@
+//! Integration with the [`log`] crate, enabled by the `log` feature
+//!
+//! Records are printed above all progress bars, so logging in a long loop
+//! doesn't corrupt the progress bars on screen.
+//!
+//! # Examples
+//! ```
+//! use avance::{logger::AvanceLogger, AvanceBar};
+//!
+//! AvanceLogger::new().init().unwrap();
+//!
+//! let pb = AvanceBar::new(100);
+//! for i in pb.with_iter(0..100) {
+//!     if i == 42 {
+//!         log::info!("found the answer");
+//!     }
+//! }
+//! ```
+
+use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::io::{stderr, Write};
+
+use crate::group::suspend_all;
+
+/// A logger which writes records above all progress bars
+///
+/// It either wraps an existing logger, or formats records to the standard
+/// error like `[INFO  target] message`.
+pub struct AvanceLogger {
+    inner: Box<dyn Log>,
+    max_level: LevelFilter,
+}
+
+impl AvanceLogger {
+    /// Create a logger with the built-in formatter, which shows records
+    /// of the level `INFO` and above.
+    pub fn new() -> Self {
+        Self {
+            inner: Box::new(SimpleLogger {
+                level: LevelFilter::Info,
+            }),
+            max_level: LevelFilter::Info,
+        }
+    }
+
+    /// Wrap an existing logger, which decides what to log and how to format records.
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::logger::AvanceLogger;
+    /// # struct MyLogger;
+    /// # impl log::Log for MyLogger {
+    /// #     fn enabled(&self, _: &log::Metadata) -> bool { true }
+    /// #     fn log(&self, _: &log::Record) {}
+    /// #     fn flush(&self) {}
+    /// # }
+    /// let logger = AvanceLogger::wrap(MyLogger);
+    /// ```
+    pub fn wrap(inner: impl Log + 'static) -> Self {
+        Self {
+            inner: Box::new(inner),
+            max_level: LevelFilter::Trace,
+        }
+    }
+
+    /// Builder-like function for the maximum level of records to log
+    /// (default: `INFO` for the built-in formatter, `TRACE` for a wrapped logger).
+    pub fn with_max_level(mut self, level: LevelFilter) -> Self {
+        self.max_level = level;
+        self
+    }
+
+    /// Install the logger as the global logger of the [`log`] crate.
+    ///
+    /// An error is returned if a global logger has already been set.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        let max_level = self.max_level;
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
+
+impl Default for AvanceLogger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Log for AvanceLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.max_level && self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            suspend_all(|| {
+                self.inner.log(record);
+                self.inner.flush();
+            });
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// The built-in formatter, which writes records to the standard error
+struct SimpleLogger {
+    level: LevelFilter,
+}
+
+impl Log for SimpleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        let _ = writeln!(
+            stderr().lock(),
+            "[{:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {
+        let _ = stderr().flush();
+    }
+}
@@ -0,0 +1,105 @@
+//! A background monitor which keeps progress bars refreshing
+//!
+//! A progress bar is only redrawn when it advances. If an iteration takes minutes,
+//! the elapsed time and rate would be frozen on the screen. The monitor redraws all
+//! progress bars at a steady rate to keep them moving.
+//!
+//! Drawing a progress bar takes a lock, formats a line and writes to the terminal, all in
+//! the thread which advances it. With a render thread, advancing a bar only adds to a
+//! counter, and a single thread draws all progress bars instead.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::group::{redraw_all, Group};
+
+// Interval (in nanoseconds) between two redraws. Zero means disabled.
+static INTERVAL: AtomicU64 = AtomicU64::new(0);
+// Whether the monitor thread is running
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Start a background thread which redraws all progress bars every `interval`.
+///
+/// Calling it again changes the interval of the running monitor.
+///
+/// # Examples
+/// ```
+/// # use avance::{enable_monitor, AvanceBar};
+/// # use std::time::Duration;
+/// enable_monitor(Duration::from_millis(500));
+///
+/// let pb = AvanceBar::new(10);
+/// for _ in 0..10 {
+///     // A very slow iteration
+///     pb.inc();
+/// }
+/// ```
+pub fn enable_monitor(interval: Duration) {
+    // At least 1 nanosecond, as zero stands for disabled
+    let nanos = interval.as_nanos().clamp(1, u64::MAX as u128) as u64;
+    INTERVAL.store(nanos, Ordering::Release);
+
+    if !RUNNING.swap(true, Ordering::AcqRel) {
+        thread::Builder::new()
+            .name("avance-monitor".into())
+            .spawn(run)
+            .expect("failed to spawn the monitor thread");
+    }
+}
+
+/// Stop the background monitor started by [`enable_monitor`].
+pub fn disable_monitor() {
+    INTERVAL.store(0, Ordering::Release);
+}
+
+/// Draw progress bars in a dedicated thread every `interval`, instead of in the
+/// threads advancing them.
+///
+/// This keeps [`update`](crate::AvanceBar::update) cheap when many threads advance
+/// progress bars at a high rate. Only affects progress bars created afterwards,
+/// and can't be turned off. Calling it again changes the interval.
+///
+/// See [`MultiAvance::with_render_thread`](crate::MultiAvance::with_render_thread)
+/// for a group of progress bars.
+///
+/// # Examples
+/// ```
+/// # use avance::{enable_render_thread, AvanceBar};
+/// # use std::time::Duration;
+/// enable_render_thread(Duration::from_millis(100));
+///
+/// let pb = AvanceBar::new(1000);
+/// std::thread::scope(|t| {
+///     for _ in 0..8 {
+///         t.spawn(|| {
+///             for _ in 0..1000 / 8 {
+///                 pb.inc();
+///             }
+///         });
+///     }
+/// });
+/// ```
+pub fn enable_render_thread(interval: Duration) {
+    Group::global().render(interval);
+}
+
+fn run() {
+    loop {
+        let interval = INTERVAL.load(Ordering::Acquire);
+        if interval == 0 {
+            RUNNING.store(false, Ordering::Release);
+
+            // The monitor may be enabled again before exiting
+            if INTERVAL.load(Ordering::Acquire) == 0 || RUNNING.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            continue;
+        }
+
+        thread::sleep(Duration::from_nanos(interval));
+        if INTERVAL.load(Ordering::Acquire) != 0 {
+            redraw_all();
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! A wrapped parallel iterator that shows progress, enabled by the `rayon` feature
+//!
+//! Worker threads count finished items locally, and only add them to the progress bar
+//! when it's about to be redrawn. So the progress bar stays cheap under heavy contention.
+
+use std::borrow::Cow;
+
+use rayon::iter::plumbing::{Consumer, Folder, Producer, ProducerCallback, UnindexedConsumer};
+use rayon::iter::{IndexedParallelIterator, ParallelIterator};
+
+use super::*;
+
+/// A parallel iterator wrapper that shows a progress bar
+#[derive(Debug)]
+pub struct AvanceParIter<Iter> {
+    iter: Iter,
+    bar: AvanceBar,
+}
+
+/// Wrap a parallel iterator to display its progress
+pub trait ParallelAvanceIterator
+where
+    Self: Sized + ParallelIterator,
+{
+    /// Wrap a parallel iterator to display its progress, using the length
+    /// of an indexed iterator as the total length of the progress bar.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use avance::ParallelAvanceIterator;
+    /// use rayon::prelude::*;
+    ///
+    /// let sum: u64 = (0..1000u64).into_par_iter().avance().sum();
+    /// ```
+    fn avance(self) -> AvanceParIter<Self> {
+        AvanceParIter {
+            bar: AvanceBar::with_hint(self.opt_len()),
+            iter: self,
+        }
+    }
+}
+
+// Implement ParallelAvanceIterator trait for all ParallelIterator types
+impl<Iter: ParallelIterator> ParallelAvanceIterator for Iter {}
+
+impl<Iter: ParallelIterator> AvanceParIter<Iter> {
+    /// Set the style of a progress bar.
+    ///
+    /// See [AvanceBar::with_style]
+    pub fn with_style(self, style: Style) -> Self {
+        self.bar.set_style(style);
+        self
+    }
+
+    /// Set the user-custom style of a progress bar.
+    ///
+    /// See [AvanceBar::with_style_str]
+    pub fn with_style_str(self, s: &'static str) -> Self {
+        self.bar.set_style_str(s);
+        self
+    }
+
+    /// Set the description of a progress bar.
+    ///
+    /// See [AvanceBar::with_desc]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use avance::ParallelAvanceIterator;
+    /// use rayon::prelude::*;
+    ///
+    /// (0..1000).into_par_iter().avance().with_desc("task name").for_each(|_| {
+    ///     // ...
+    /// });
+    /// ```
+    pub fn with_desc(self, desc: impl Into<Cow<'static, str>>) -> Self {
+        self.bar.set_desc(desc);
+        self
+    }
+
+    /// Displaying numbers in a human readable format, using SI metric prefix
+    /// (k = 10^3, M = 10^6, etc.) if `true` is given.
+    ///
+    /// See [AvanceBar::with_unit_scale]
+    pub fn with_unit_scale(self, unit_scale: impl Into<UnitScale>) -> Self {
+        self.bar.set_unit_scale(unit_scale);
+        self
+    }
+
+    /// Set the spinner of a progress bar, which is shown when the length
+    /// of the iterator is unknown.
+    ///
+    /// See [AvanceBar::with_spinner]
+    pub fn with_spinner(self, spinner: Spinner) -> Self {
+        self.bar.set_spinner(spinner);
+        self
+    }
+
+    /// Show a bouncing animation when the length of the iterator is unknown.
+    ///
+    /// See [AvanceBar::with_bounce]
+    pub fn with_bounce(self, bounce: bool) -> Self {
+        self.bar.set_bounce(bounce);
+        self
+    }
+
+    /// Set the colors of a progress bar.
+    ///
+    /// See [AvanceBar::with_colors]
+    pub fn with_colors(self, colors: Colors) -> Self {
+        self.bar.set_colors(colors);
+        self
+    }
+
+    /// Whether to leave the progress bar on the terminal once completed (default: true).
+    ///
+    /// See [AvanceBar::with_leave]
+    pub fn with_leave(self, leave: bool) -> Self {
+        self.bar.set_leave(leave);
+        self
+    }
+
+    /// Set the unit name (default: `it`) of a progress bar.
+    ///
+    /// See [AvanceBar::with_unit]
+    pub fn with_unit(self, unit: impl Into<Cow<'static, str>>) -> Self {
+        self.bar.set_unit(unit);
+        self
+    }
+
+    /// Set a progress bar's width
+    ///
+    /// See [AvanceBar::with_width]
+    pub fn with_width(self, width: u16) -> Self {
+        self.bar.set_width(width);
+        self
+    }
+}
+
+impl<Iter: ParallelIterator> ParallelIterator for AvanceParIter<Iter> {
+    type Item = Iter::Item;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        let consumer = AvanceConsumer {
+            base: consumer,
+            bar: &self.bar,
+        };
+        self.iter.drive_unindexed(consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        self.iter.opt_len()
+    }
+}
+
+impl<Iter: IndexedParallelIterator> IndexedParallelIterator for AvanceParIter<Iter> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        let consumer = AvanceConsumer {
+            base: consumer,
+            bar: &self.bar,
+        };
+        self.iter.drive(consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        return self.iter.with_producer(Callback {
+            callback,
+            bar: self.bar,
+        });
+
+        struct Callback<CB> {
+            callback: CB,
+            bar: AvanceBar,
+        }
+
+        impl<T, CB: ProducerCallback<T>> ProducerCallback<T> for Callback<CB> {
+            type Output = CB::Output;
+
+            fn callback<P: Producer<Item = T>>(self, base: P) -> CB::Output {
+                let producer = AvanceProducer {
+                    base,
+                    bar: &self.bar,
+                };
+                self.callback.callback(producer)
+            }
+        }
+    }
+}
+
+/// Items finished by a worker, which haven't been added to the progress bar
+struct Pending<'b> {
+    bar: &'b AvanceBar,
+    n: u64,
+}
+
+impl<'b> Pending<'b> {
+    fn new(bar: &'b AvanceBar) -> Self {
+        Self { bar, n: 0 }
+    }
+
+    fn inc(&mut self) {
+        self.n += 1;
+        // Checking whether to redraw doesn't write to shared memory, and
+        // adding to a rendered bar doesn't draw it
+        if self.bar.is_rendered() || self.bar.ready() {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.n > 0 {
+            self.bar.update(self.n);
+            self.n = 0;
+        }
+    }
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+struct AvanceProducer<'b, P> {
+    base: P,
+    bar: &'b AvanceBar,
+}
+
+impl<'b, P: Producer> Producer for AvanceProducer<'b, P> {
+    type Item = P::Item;
+    type IntoIter = AvanceProducerIter<'b, P::IntoIter>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        AvanceProducerIter {
+            iter: self.base.into_iter(),
+            pending: Pending::new(self.bar),
+        }
+    }
+
+    fn min_len(&self) -> usize {
+        self.base.min_len()
+    }
+
+    fn max_len(&self) -> usize {
+        self.base.max_len()
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = self.base.split_at(index);
+        (
+            AvanceProducer {
+                base: left,
+                bar: self.bar,
+            },
+            AvanceProducer {
+                base: right,
+                bar: self.bar,
+            },
+        )
+    }
+}
+
+struct AvanceProducerIter<'b, Iter> {
+    iter: Iter,
+    pending: Pending<'b>,
+}
+
+impl<Iter: Iterator> Iterator for AvanceProducerIter<'_, Iter> {
+    type Item = Iter::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.iter.next();
+        if next.is_some() {
+            self.pending.inc();
+        }
+        next
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<Iter: ExactSizeIterator> ExactSizeIterator for AvanceProducerIter<'_, Iter> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+impl<Iter: DoubleEndedIterator> DoubleEndedIterator for AvanceProducerIter<'_, Iter> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let next = self.iter.next_back();
+        if next.is_some() {
+            self.pending.inc();
+        }
+        next
+    }
+}
+
+struct AvanceConsumer<'b, C> {
+    base: C,
+    bar: &'b AvanceBar,
+}
+
+impl<'b, T, C: Consumer<T>> Consumer<T> for AvanceConsumer<'b, C> {
+    type Folder = AvanceFolder<'b, C::Folder>;
+    type Reducer = C::Reducer;
+    type Result = C::Result;
+
+    fn split_at(self, index: usize) -> (Self, Self, Self::Reducer) {
+        let (left, right, reducer) = self.base.split_at(index);
+        (
+            AvanceConsumer {
+                base: left,
+                bar: self.bar,
+            },
+            AvanceConsumer {
+                base: right,
+                bar: self.bar,
+            },
+            reducer,
+        )
+    }
+
+    fn into_folder(self) -> Self::Folder {
+        AvanceFolder {
+            base: self.base.into_folder(),
+            pending: Pending::new(self.bar),
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+impl<T, C: UnindexedConsumer<T>> UnindexedConsumer<T> for AvanceConsumer<'_, C> {
+    fn split_off_left(&self) -> Self {
+        AvanceConsumer {
+            base: self.base.split_off_left(),
+            bar: self.bar,
+        }
+    }
+
+    fn to_reducer(&self) -> Self::Reducer {
+        self.base.to_reducer()
+    }
+}
+
+struct AvanceFolder<'b, F> {
+    base: F,
+    pending: Pending<'b>,
+}
+
+impl<T, F: Folder<T>> Folder<T> for AvanceFolder<'_, F> {
+    type Result = F::Result;
+
+    fn consume(mut self, item: T) -> Self {
+        self.pending.inc();
+        AvanceFolder {
+            base: self.base.consume(item),
+            pending: self.pending,
+        }
+    }
+
+    fn complete(self) -> Self::Result {
+        self.base.complete()
+    }
+
+    fn full(&self) -> bool {
+        self.base.full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use crate::{AvanceBar, ParallelAvanceIterator};
+
+    #[test]
+    fn par_iter() {
+        let iter = (0..10_000u64).into_par_iter().avance();
+        let bar: AvanceBar = iter.bar.clone();
+        assert_eq!(bar.snapshot().total, Some(10_000));
+        assert_eq!(iter.map(|i| i * 2).sum::<u64>(), 99_990_000);
+        assert_eq!(bar.snapshot().n, 10_000);
+
+        // The length of a filtered iterator is unknown
+        let iter = (0..10_000u64)
+            .into_par_iter()
+            .filter(|i| i % 2 == 0)
+            .avance();
+        let bar = iter.bar.clone();
+        assert_eq!(bar.snapshot().total, None);
+        assert_eq!(iter.count(), 5_000);
+        assert_eq!(bar.snapshot().n, 5_000);
+
+        let v: Vec<_> = (0..1000).into_par_iter().avance().rev().collect();
+        assert_eq!(v[0], 999);
+    }
+}
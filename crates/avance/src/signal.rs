@@ -0,0 +1,99 @@
+//! Signal handling on Unix, enabled by the `signal` feature
+//!
+//! When the terminal is resized, progress bars are redrawn at the new size immediately,
+//! instead of on their next update.
+//!
+//! Progress bars can also restore the terminal when the process is interrupted,
+//! see [`handle_interrupts`].
+
+use std::io::Result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+
+use signal_hook::consts::{SIGINT, SIGTERM, SIGWINCH};
+use signal_hook::iterator::Signals;
+use signal_hook::low_level::emulate_default_handler;
+
+use crate::group::{interrupt_all, redraw_all};
+
+// Whether to abandon progress bars when interrupted
+static ABANDON: AtomicBool = AtomicBool::new(false);
+// Whether to terminate the process when interrupted
+static TERMINATE: AtomicBool = AtomicBool::new(false);
+// Whether interrupts are being handled
+static HANDLING: Mutex<bool> = Mutex::new(false);
+
+/// Restore the terminal when the process receives SIGINT (Ctrl-C) or SIGTERM.
+///
+/// Without it, an interrupted process leaves the cursor among the progress bars,
+/// and the shell prompt overwrites them. On an interrupt, the cursor is moved below
+/// all progress bars, which are [abandoned](crate::AvanceBar::abandon) first if
+/// `abandon` is true.
+///
+/// Handlers the application registered before, or registers later through
+/// `signal-hook` (as tokio does), still run on an interrupt. Since avance cannot
+/// tell whether they exit, the process is only terminated if `terminate` is true,
+/// by taking the default action of the signal afterwards. Pass `false` when the
+/// application handles the signals itself, otherwise Ctrl-C no longer stops it.
+///
+/// Calling it again changes whether bars are abandoned and the process terminated.
+///
+/// # Examples
+/// ```
+/// # use avance::{handle_interrupts, AvanceBar};
+/// handle_interrupts(true, true).unwrap();
+///
+/// let pb = AvanceBar::new(1000);
+/// for _ in 0..1000 {
+///     // Interrupted bars are left on the terminal, marked as failed
+///     pb.inc();
+/// }
+/// ```
+pub fn handle_interrupts(abandon: bool, terminate: bool) -> Result<()> {
+    ABANDON.store(abandon, Ordering::Relaxed);
+    TERMINATE.store(terminate, Ordering::Relaxed);
+
+    let mut handling = HANDLING.lock().unwrap();
+    if *handling {
+        return Ok(());
+    }
+
+    let mut signals = Signals::new([SIGINT, SIGTERM])?;
+    thread::Builder::new()
+        .name("avance-interrupt".into())
+        .spawn(move || {
+            for signal in signals.forever() {
+                interrupt_all(ABANDON.load(Ordering::Relaxed), || {
+                    if TERMINATE.load(Ordering::Relaxed) {
+                        emulate_default_handler(signal).expect("failed to raise the signal");
+                    }
+                });
+            }
+        })?;
+    *handling = true;
+
+    Ok(())
+}
+
+/// Start a background thread which redraws all progress bars when the terminal
+/// is resized. Only the first call has effect.
+pub(crate) fn listen_resize() {
+    static LISTENING: Once = Once::new();
+
+    LISTENING.call_once(|| {
+        let mut signals = match Signals::new([SIGWINCH]) {
+            Ok(signals) => signals,
+            Err(_) => return,
+        };
+
+        // Bars still notice the new size on their next update if this fails
+        let _ = thread::Builder::new()
+            .name("avance-resize".into())
+            .spawn(move || {
+                for _ in signals.forever() {
+                    redraw_all();
+                }
+            });
+    });
+}
@@ -0,0 +1,226 @@
+//! A wrapped stream that shows progress, enabled by the `futures` feature
+
+use std::borrow::Cow;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+use super::*;
+
+pin_project! {
+    /// A stream wrapper that shows a progress bar
+    pub struct AvanceStreamer<S> {
+        #[pin]
+        stream: S,
+        bar: AvanceBar,
+    }
+}
+
+/// Wrap a stream to display its progress
+pub trait AvanceStream
+where
+    Self: Sized + Stream,
+{
+    /// Wrap a stream to display its progress, using the upper bound
+    /// of stream's size as the total length of the progress bar.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use avance::AvanceStream;
+    /// use futures::{executor::block_on, stream, StreamExt};
+    ///
+    /// block_on(async {
+    ///     let mut tasks = stream::iter(0..1000).avance();
+    ///     while let Some(_) = tasks.next().await {
+    ///         // ...
+    ///     }
+    /// });
+    /// ```
+    fn avance(self) -> AvanceStreamer<Self> {
+        AvanceStreamer {
+            bar: AvanceBar::with_hint(self.size_hint().1),
+            stream: self,
+        }
+    }
+}
+
+impl<S: Stream> AvanceStreamer<S> {
+    /// Set the style of a progress bar.
+    ///
+    /// See [AvanceBar::with_style]
+    pub fn with_style(self, style: Style) -> Self {
+        self.bar.set_style(style);
+        self
+    }
+
+    /// Set the user-custom style of a progress bar.
+    ///
+    /// See [AvanceBar::with_style_str]
+    pub fn with_style_str(self, s: &'static str) -> Self {
+        self.bar.set_style_str(s);
+        self
+    }
+
+    /// Set the description of a progress bar.
+    ///
+    /// See [AvanceBar::with_desc]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use avance::AvanceStream;
+    /// # use futures::stream;
+    /// let tasks = stream::iter(0..1000).avance().with_desc("task name");
+    /// ```
+    pub fn with_desc(self, desc: impl Into<Cow<'static, str>>) -> Self {
+        self.bar.set_desc(desc);
+        self
+    }
+
+    /// Displaying numbers in a human readable format, using SI metric prefix
+    /// (k = 10^3, M = 10^6, etc.) if `true` is given.
+    ///
+    /// See [AvanceBar::with_unit_scale]
+    pub fn with_unit_scale(self, unit_scale: impl Into<UnitScale>) -> Self {
+        self.bar.set_unit_scale(unit_scale);
+        self
+    }
+
+    /// Set the spinner of a progress bar, which is shown when the length
+    /// of the stream is unknown.
+    ///
+    /// See [AvanceBar::with_spinner]
+    pub fn with_spinner(self, spinner: Spinner) -> Self {
+        self.bar.set_spinner(spinner);
+        self
+    }
+
+    /// Show a bouncing animation when the length of the stream is unknown.
+    ///
+    /// See [AvanceBar::with_bounce]
+    pub fn with_bounce(self, bounce: bool) -> Self {
+        self.bar.set_bounce(bounce);
+        self
+    }
+
+    /// Set the colors of a progress bar.
+    ///
+    /// See [AvanceBar::with_colors]
+    pub fn with_colors(self, colors: Colors) -> Self {
+        self.bar.set_colors(colors);
+        self
+    }
+
+    /// Whether to leave the progress bar on the terminal once completed (default: true).
+    ///
+    /// See [AvanceBar::with_leave]
+    pub fn with_leave(self, leave: bool) -> Self {
+        self.bar.set_leave(leave);
+        self
+    }
+
+    /// Set the unit name (default: `it`) of a progress bar.
+    ///
+    /// See [AvanceBar::with_unit]
+    pub fn with_unit(self, unit: impl Into<Cow<'static, str>>) -> Self {
+        self.bar.set_unit(unit);
+        self
+    }
+
+    /// Set a progress bar's width
+    ///
+    /// See [AvanceBar::with_width]
+    pub fn with_width(self, width: u16) -> Self {
+        self.bar.set_width(width);
+        self
+    }
+
+    /// Creates a stream which gives the original item and a progress bar handler.
+    ///
+    /// See [AvanceIter::with_pb]
+    ///
+    /// # Examples
+    /// ```
+    /// # use avance::AvanceStream;
+    /// use futures::{executor::block_on, stream, StreamExt};
+    ///
+    /// block_on(async {
+    ///     let mut tasks = stream::iter(0..1000).avance().with_pb();
+    ///     while let Some((_, pb)) = tasks.next().await {
+    ///         pb.set_postfix("");
+    ///     }
+    /// });
+    /// ```
+    pub fn with_pb(self) -> AvanceBarStream<S> {
+        AvanceBarStream { inner: self }
+    }
+}
+
+// Implement AvanceStream trait for all Stream types
+impl<S: Stream> AvanceStream for S {}
+
+impl<S: Stream> Stream for AvanceStreamer<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let next = this.stream.poll_next(cx);
+        if let Poll::Ready(Some(_)) = next {
+            this.bar.inc();
+        }
+        next
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+pin_project! {
+    /// Wraps an AvanceStreamer and gives a progress bar handler when polled.
+    pub struct AvanceBarStream<S> {
+        #[pin]
+        inner: AvanceStreamer<S>,
+    }
+}
+
+impl<S: Stream> Stream for AvanceBarStream<S> {
+    type Item = (S::Item, AvanceBar);
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let inner = self.project().inner;
+        let bar = inner.bar.clone();
+        inner.poll_next(cx).map(|next| next.map(|item| (item, bar)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{executor::block_on, stream, StreamExt};
+
+    use crate::{AvanceStream, DrawTarget};
+
+    #[test]
+    fn stream() {
+        let tasks = stream::iter(0..100).avance();
+        let bar = tasks.bar.clone().with_draw_target(DrawTarget::Hidden);
+        assert_eq!(bar.snapshot().total, Some(100));
+
+        let sum = block_on(tasks.fold(0, |sum, i| async move { sum + i }));
+        assert_eq!(sum, 4950);
+        assert_eq!(bar.snapshot().n, 100);
+
+        let unknown = stream::iter(0..).take_while(|&i| futures::future::ready(i < 10));
+        let items: Vec<_> = block_on(unknown.avance().with_pb().collect());
+        assert_eq!(items.len(), 10);
+        assert_eq!(items[0].1.snapshot().total, None);
+        assert_eq!(items[9].1.snapshot().n, 10);
+    }
+}
@@ -2,6 +2,14 @@
 
 use std::borrow::Cow;
 
+#[cfg(has_std_once_cell = "false")]
+use once_cell::sync::OnceCell as OnceLock;
+#[cfg(has_std_once_cell = "true")]
+use std::sync::OnceLock;
+
+#[doc(no_inline)]
+pub use crossterm::style::Color;
+
 /// Styles of a progress bar
 #[derive(Debug, Clone, Default)]
 pub enum Style {
@@ -29,3 +37,257 @@ impl AsRef<str> for Style {
         }
     }
 }
+
+/// Spinners for progress bars of unknown length
+///
+/// A spinner advances with time, so it keeps moving between two increments
+/// as long as the progress bar is redrawn (see [`enable_monitor`](crate::enable_monitor)).
+#[derive(Debug, Clone, Default)]
+pub enum Spinner {
+    /// Presentation: `⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏`
+    #[default]
+    Dots,
+
+    /// Presentation: `|/-\`
+    Line,
+
+    /// Presentation: `◐◓◑◒`
+    Circle,
+
+    /// Presentation: `←↖↑↗→↘↓↙`
+    Arrow,
+
+    /// User custom frames
+    Custom(Vec<Cow<'static, str>>),
+}
+
+impl Spinner {
+    /// The frame to show at the `i`-th tick
+    pub fn frame(&self, i: usize) -> &str {
+        let frames: &[&str] = match self {
+            Self::Dots => &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+            Self::Line => &["|", "/", "-", "\\"],
+            Self::Circle => &["◐", "◓", "◑", "◒"],
+            Self::Arrow => &["←", "↖", "↑", "↗", "→", "↘", "↓", "↙"],
+            Self::Custom(frames) if frames.is_empty() => return "",
+            Self::Custom(frames) => return &frames[i % frames.len()],
+        };
+
+        frames[i % frames.len()]
+    }
+}
+
+/// Prefixes for displaying human readable numbers
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnitScale {
+    /// Presentation: `1234567`
+    #[default]
+    None,
+
+    /// SI metric prefixes (k = 10^3, M = 10^6, etc.)
+    ///
+    /// Presentation: `1.23M`
+    SI,
+
+    /// IEC binary prefixes (Ki = 2^10, Mi = 2^20, etc.)
+    ///
+    /// Presentation: `1.18Mi`
+    IEC,
+}
+
+impl From<bool> for UnitScale {
+    /// `true` stands for SI metric prefixes, and `false` for no prefixes.
+    fn from(unit_scale: bool) -> Self {
+        match unit_scale {
+            true => Self::SI,
+            false => Self::None,
+        }
+    }
+}
+
+/// Colors of a progress bar (default: no colors)
+///
+/// Colors are only shown on terminals, and never if the `NO_COLOR`
+/// environment variable is set.
+///
+/// # Examples
+/// ```
+/// # use avance::{AvanceBar, Color, Colors};
+/// let colors = Colors::new()
+///     .with_filled(Color::Cyan)
+///     .with_background(Color::DarkGrey)
+///     .with_done(Color::Green)
+///     .with_failed(Color::Red);
+/// let pb = AvanceBar::new(1000).with_colors(colors);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Colors {
+    filled: Option<Color>,
+    background: Option<Color>,
+    desc: Option<Color>,
+    stats: Option<Color>,
+    done: Option<Color>,
+    failed: Option<Color>,
+    gradient: Option<(Color, Color)>,
+}
+
+impl Colors {
+    /// Create a set of colors, where nothing is colored
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builder-like function for the color of the filled part of a bar
+    pub fn with_filled(mut self, color: Color) -> Self {
+        self.filled = Some(color);
+        self
+    }
+
+    /// Builder-like function for the color of the unfilled part of a bar
+    pub fn with_background(mut self, color: Color) -> Self {
+        self.background = Some(color);
+        self
+    }
+
+    /// Builder-like function for the color of the description
+    pub fn with_desc(mut self, color: Color) -> Self {
+        self.desc = Some(color);
+        self
+    }
+
+    /// Builder-like function for the color of the statistics, such as
+    /// the percentage, counts, time and rate
+    pub fn with_stats(mut self, color: Color) -> Self {
+        self.stats = Some(color);
+        self
+    }
+
+    /// Builder-like function for the color of the filled part once
+    /// a progress bar is completed
+    pub fn with_done(mut self, color: Color) -> Self {
+        self.done = Some(color);
+        self
+    }
+
+    /// Builder-like function for the color of the filled part if a
+    /// progress bar is abandoned, or dropped during a panic
+    pub fn with_failed(mut self, color: Color) -> Self {
+        self.failed = Some(color);
+        self
+    }
+
+    /// Builder-like function for a filled part whose color changes from `from`
+    /// to `to` with the percentage, which overrides [`with_filled`](Self::with_filled)
+    /// for bars of known length.
+    ///
+    /// Only [`Color::Rgb`] can be interpolated, otherwise the color
+    /// jumps from `from` to `to` on completion.
+    pub fn with_gradient(mut self, from: Color, to: Color) -> Self {
+        self.gradient = Some((from, to));
+        self
+    }
+
+    pub(crate) fn desc(&self) -> Option<Color> {
+        self.desc
+    }
+
+    pub(crate) fn stats(&self) -> Option<Color> {
+        self.stats
+    }
+
+    pub(crate) fn background(&self) -> Option<Color> {
+        self.background
+    }
+
+    /// The color of the filled part, given the finished fraction (if known)
+    /// and the status of the bar
+    pub(crate) fn filled(&self, pct: Option<f64>, status: Status) -> Option<Color> {
+        match status {
+            Status::Done if self.done.is_some() => self.done,
+            Status::Failed if self.failed.is_some() => self.failed,
+            _ => match (self.gradient, pct) {
+                (Some((from, to)), Some(pct)) => Some(lerp(from, to, pct)),
+                _ => self.filled,
+            },
+        }
+    }
+}
+
+/// The status of a progress bar, which decides the color of the filled part
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Status {
+    InProgress,
+    Done,
+    Failed,
+}
+
+/// Whether the `NO_COLOR` environment variable is set, which is read once
+#[cfg_attr(has_std_once_cell = "true", allow(clippy::incompatible_msrv))]
+pub(crate) fn no_color() -> bool {
+    static NO_COLOR: OnceLock<bool> = OnceLock::new();
+    *NO_COLOR.get_or_init(|| std::env::var_os("NO_COLOR").map_or(false, |v| !v.is_empty()))
+}
+
+/// Interpolate between two colors
+fn lerp(from: Color, to: Color, pct: f64) -> Color {
+    match (from, to) {
+        (
+            Color::Rgb {
+                r: r1,
+                g: g1,
+                b: b1,
+            },
+            Color::Rgb {
+                r: r2,
+                g: g2,
+                b: b2,
+            },
+        ) => {
+            let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * pct).round() as u8;
+            Color::Rgb {
+                r: mix(r1, r2),
+                g: mix(g1, g2),
+                b: mix(b1, b2),
+            }
+        }
+        _ if pct >= 1.0 => to,
+        _ => from,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors() {
+        let black = Color::Rgb { r: 0, g: 0, b: 0 };
+        let white = Color::Rgb {
+            r: 255,
+            g: 255,
+            b: 255,
+        };
+        let colors = Colors::new()
+            .with_filled(Color::Blue)
+            .with_gradient(black, white)
+            .with_failed(Color::Red);
+
+        assert_eq!(colors.filled(None, Status::InProgress), Some(Color::Blue));
+        assert_eq!(
+            colors.filled(Some(0.5), Status::InProgress),
+            Some(Color::Rgb {
+                r: 128,
+                g: 128,
+                b: 128
+            })
+        );
+        assert_eq!(colors.filled(Some(1.0), Status::Done), Some(white));
+        assert_eq!(colors.filled(Some(0.5), Status::Failed), Some(Color::Red));
+
+        let colors = colors.with_done(Color::Green);
+        assert_eq!(colors.filled(Some(1.0), Status::Done), Some(Color::Green));
+        assert_eq!(colors.filled(None, Status::Done), Some(Color::Green));
+
+        assert_eq!(lerp(Color::Blue, Color::Green, 0.9), Color::Blue);
+    }
+}
@@ -0,0 +1,143 @@
+//! Where to draw progress bars
+
+use crossterm::tty::IsTty;
+use std::fmt::{self, Debug, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{stderr, stdout, Result, Write};
+use std::sync::Mutex;
+
+#[cfg(has_std_once_cell = "false")]
+use once_cell::sync::OnceCell as OnceLock;
+#[cfg(has_std_once_cell = "true")]
+use std::sync::OnceLock;
+
+/// The target that a progress bar is drawn to
+///
+/// # Examples
+/// ```
+/// # use avance::{AvanceBar, DrawTarget};
+/// let pb = AvanceBar::new(100).with_draw_target(DrawTarget::Stdout);
+/// ```
+#[derive(Default)]
+pub enum DrawTarget {
+    /// Draw to the standard error
+    #[default]
+    Stderr,
+
+    /// Draw to the standard output
+    Stdout,
+
+    /// Draw to the controlling terminal (`/dev/tty` or `CONOUT$` on windows),
+    /// even if the standard streams are redirected.
+    Tty,
+
+    /// Draw to an arbitrary writer, which is treated as a terminal of 80 columns
+    /// and 64 rows.
+    ///
+    /// Useful for capturing frames in tests.
+    Writer(Box<dyn Write + Send>),
+
+    /// Draw nothing
+    Hidden,
+}
+
+impl DrawTarget {
+    /// Create a target from an arbitrary writer
+    pub fn writer(writer: impl Write + Send + 'static) -> Self {
+        Self::Writer(Box::new(writer))
+    }
+
+    /// Whether the target is an interactive terminal
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Self::Stderr => stderr().is_tty(),
+            Self::Stdout => stdout().is_tty(),
+            Self::Tty => tty().is_some(),
+            Self::Writer(_) => true,
+            Self::Hidden => false,
+        }
+    }
+
+    /// The size of the terminal in columns and rows, or None if the terminal has
+    /// to be queried. Writers have a fixed size, which saves querying a terminal
+    /// that may not exist on every frame.
+    pub(crate) fn fixed_size(&self) -> Option<(u16, u16)> {
+        match self {
+            Self::Writer(_) => Some(FALLBACK_SIZE),
+            _ => None,
+        }
+    }
+
+    /// Whether the target draws nothing
+    pub fn is_hidden(&self) -> bool {
+        matches!(self, Self::Hidden)
+    }
+
+    /// A target drawing to the same place, if possible. Writers can't be shared,
+    /// which fall back to the default target.
+    pub(crate) fn share(&self) -> Self {
+        match self {
+            Self::Stderr => Self::Stderr,
+            Self::Stdout => Self::Stdout,
+            Self::Tty => Self::Tty,
+            Self::Writer(_) => Self::default(),
+            Self::Hidden => Self::Hidden,
+        }
+    }
+
+    /// Run `f` with exclusive access to the underlying writer
+    pub(crate) fn with_writer<F>(&mut self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<()>,
+    {
+        match self {
+            Self::Stderr => f(&mut stderr().lock()),
+            Self::Stdout => f(&mut stdout().lock()),
+            Self::Tty => match tty() {
+                Some(tty) => f(&mut *tty.lock().unwrap()),
+                None => Ok(()),
+            },
+            Self::Writer(w) => f(w),
+            Self::Hidden => Ok(()),
+        }
+    }
+}
+
+impl Debug for DrawTarget {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stderr => f.write_str("Stderr"),
+            Self::Stdout => f.write_str("Stdout"),
+            Self::Tty => f.write_str("Tty"),
+            Self::Writer(_) => f.write_str("Writer(..)"),
+            Self::Hidden => f.write_str("Hidden"),
+        }
+    }
+}
+
+// The size of a terminal which can't be queried, in columns and rows
+const FALLBACK_SIZE: (u16, u16) = (80, 64);
+
+/// Query the size of the terminal, in columns and rows. It may run a process
+/// if there's no terminal, so don't hold any lock meanwhile.
+pub(crate) fn terminal_size() -> (u16, u16) {
+    crossterm::terminal::size().unwrap_or(FALLBACK_SIZE)
+}
+
+// The controlling terminal, opened on first use
+#[cfg_attr(has_std_once_cell = "true", allow(clippy::incompatible_msrv))]
+static TTY: OnceLock<Option<Mutex<File>>> = OnceLock::new();
+
+#[cfg_attr(has_std_once_cell = "true", allow(clippy::incompatible_msrv))]
+fn tty() -> Option<&'static Mutex<File>> {
+    TTY.get_or_init(|| {
+        let path = if cfg!(windows) { "CONOUT$" } else { "/dev/tty" };
+        OpenOptions::new()
+            .write(true)
+            .open(path)
+            .ok()
+            .filter(|f| f.is_tty())
+            .map(Mutex::new)
+    })
+    .as_ref()
+}
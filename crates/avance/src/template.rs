@@ -0,0 +1,311 @@
+//! User-defined templates for rendering a progress bar
+//!
+//! A template is a string made of literal text and placeholders wrapped in
+//! braces, such as `"{desc} {bar:40} {pos}/{len} {rate} eta {eta}"`.
+//!
+//! Available placeholders:
+//! - `desc`: the description of a progress bar
+//! - `bar`: the bar itself, see [`Style`](crate::Style)
+//! - `pos`: the number of finished iterations
+//! - `len`: the total length, or `?` if unknown
+//! - `percent`: the percentage of finished iterations, from 0 to 100
+//! - `elapsed`: elapsed time
+//! - `eta`: estimated remaining time, or `?` if unknown
+//! - `rate`: the number of iterations per second, such as `34.50it/s` or `2.50s/it`
+//! - `postfix`: the postfix of a progress bar
+//! - `spinner`: a spinner, see [`Spinner`](crate::Spinner)
+//!
+//! A placeholder may carry a spec after a colon: `{name:[align][width]}`,
+//! where `align` is one of `<` (left), `>` (right) or `^` (center). The width
+//! of `bar` is the width of the bar itself; without a width, the bar takes up
+//! the remaining space. If the total length is unknown, `bar` shows a bouncing
+//! animation. Use `{{` and `}}` for literal braces.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A parsed template for rendering a progress bar
+///
+/// # Examples
+/// ```
+/// # use avance::template::BarTemplate;
+/// let template: BarTemplate = "{desc:>10} {bar:40} {pos}/{len}".parse().unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BarTemplate {
+    pub(crate) segments: Vec<Segment>,
+}
+
+impl BarTemplate {
+    /// Parse a template string. See the [module documentation](self) for syntax.
+    pub fn parse(s: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.char_indices().peekable();
+        let mut flexible_bar = false;
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' if matches!(chars.peek(), Some((_, '{'))) => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if matches!(chars.peek(), Some((_, '}'))) => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(TemplateError::new(i, ErrorKind::UnmatchedBrace)),
+                '{' => {
+                    let mut body = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((j, '{')) => {
+                                return Err(TemplateError::new(j, ErrorKind::UnmatchedBrace))
+                            }
+                            Some((_, c)) => body.push(c),
+                            None => return Err(TemplateError::new(i, ErrorKind::Unclosed)),
+                        }
+                    }
+
+                    let (name, spec) = match body.split_once(':') {
+                        Some((name, spec)) => (name, Some(spec)),
+                        None => (body.as_str(), None),
+                    };
+                    let key = name
+                        .trim()
+                        .parse()
+                        .map_err(|_| TemplateError::new(i, ErrorKind::UnknownKey(name.into())))?;
+                    let spec = match spec {
+                        Some(spec) => spec.parse().map_err(|_| {
+                            TemplateError::new(i, ErrorKind::InvalidSpec(spec.into()))
+                        })?,
+                        None => Spec::default(),
+                    };
+
+                    if key == Key::Bar && spec.width.is_none() {
+                        if flexible_bar {
+                            return Err(TemplateError::new(i, ErrorKind::MultipleBars));
+                        }
+                        flexible_bar = true;
+                    }
+
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field { key, spec });
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+
+        Ok(Self { segments })
+    }
+}
+
+impl FromStr for BarTemplate {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+/// The error returned when a template is invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    offset: usize,
+    kind: ErrorKind,
+}
+
+impl TemplateError {
+    fn new(offset: usize, kind: ErrorKind) -> Self {
+        Self { offset, kind }
+    }
+
+    /// The byte offset in the template where the error occurred
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ErrorKind::Unclosed => write!(f, "unclosed placeholder"),
+            ErrorKind::UnmatchedBrace => write!(f, "unmatched brace"),
+            ErrorKind::UnknownKey(key) => write!(f, "unknown placeholder `{}`", key),
+            ErrorKind::InvalidSpec(spec) => write!(f, "invalid spec `{}`", spec),
+            ErrorKind::MultipleBars => write!(f, "more than one bar without a width"),
+        }?;
+        write!(f, " at offset {}", self.offset)
+    }
+}
+
+impl Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ErrorKind {
+    Unclosed,
+    UnmatchedBrace,
+    UnknownKey(String),
+    InvalidSpec(String),
+    MultipleBars,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Segment {
+    Literal(String),
+    Field { key: Key, spec: Spec },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Key {
+    Desc,
+    Bar,
+    Pos,
+    Len,
+    Percent,
+    Elapsed,
+    Eta,
+    Rate,
+    Postfix,
+    Spinner,
+}
+
+impl FromStr for Key {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "desc" => Self::Desc,
+            "bar" => Self::Bar,
+            "pos" => Self::Pos,
+            "len" => Self::Len,
+            "percent" => Self::Percent,
+            "elapsed" => Self::Elapsed,
+            "eta" => Self::Eta,
+            "rate" => Self::Rate,
+            "postfix" => Self::Postfix,
+            "spinner" => Self::Spinner,
+            _ => return Err(()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Spec {
+    pub align: Align,
+    pub width: Option<usize>,
+}
+
+impl Spec {
+    /// Pad a rendered field according to the spec
+    pub fn pad(&self, s: &str, out: &mut String) {
+        let len = s.chars().count();
+        let padding = self.width.map_or(0, |w| w.saturating_sub(len));
+        let (left, right) = match self.align {
+            Align::Left => (0, padding),
+            Align::Right => (padding, 0),
+            Align::Center => (padding / 2, padding - padding / 2),
+        };
+
+        out.extend(std::iter::repeat(' ').take(left));
+        out.push_str(s);
+        out.extend(std::iter::repeat(' ').take(right));
+    }
+}
+
+impl FromStr for Spec {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (align, width) = match s.chars().next() {
+            Some('<') => (Align::Left, &s[1..]),
+            Some('>') => (Align::Right, &s[1..]),
+            Some('^') => (Align::Center, &s[1..]),
+            _ => (Align::Left, s),
+        };
+        let width = match width {
+            "" => None,
+            w => Some(w.parse().map_err(|_| ())?),
+        };
+
+        Ok(Self { align, width })
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum Align {
+    #[default]
+    Left,
+    Right,
+    Center,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let template = BarTemplate::parse("{desc} {bar:40} {{{pos:>5}}}").unwrap();
+        assert_eq!(
+            template.segments,
+            vec![
+                Segment::Field {
+                    key: Key::Desc,
+                    spec: Spec::default()
+                },
+                Segment::Literal(" ".into()),
+                Segment::Field {
+                    key: Key::Bar,
+                    spec: Spec {
+                        align: Align::Left,
+                        width: Some(40)
+                    }
+                },
+                Segment::Literal(" {".into()),
+                Segment::Field {
+                    key: Key::Pos,
+                    spec: Spec {
+                        align: Align::Right,
+                        width: Some(5)
+                    }
+                },
+                Segment::Literal("}".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        let err = |s: &str| BarTemplate::parse(s).unwrap_err().kind;
+
+        assert_eq!(err("{desc"), ErrorKind::Unclosed);
+        assert_eq!(err("desc}"), ErrorKind::UnmatchedBrace);
+        assert_eq!(err("{speed}"), ErrorKind::UnknownKey("speed".into()));
+        assert_eq!(err("{pos:>x}"), ErrorKind::InvalidSpec(">x".into()));
+        assert_eq!(err("{bar} {bar}"), ErrorKind::MultipleBars);
+        assert!(BarTemplate::parse("{bar} {bar:10}").is_ok());
+    }
+
+    #[test]
+    fn pad() {
+        let pad = |spec: &str, s: &str| {
+            let mut out = String::new();
+            spec.parse::<Spec>().unwrap().pad(s, &mut out);
+            out
+        };
+
+        assert_eq!(pad("5", "ab"), "ab   ");
+        assert_eq!(pad(">5", "ab"), "   ab");
+        assert_eq!(pad("^5", "ab"), " ab  ");
+        assert_eq!(pad("1", "ab"), "ab");
+    }
+}
@@ -0,0 +1,25 @@
+//! Fixtures shared by the unit tests and the integration tests
+
+use std::io::{Result, Write};
+use std::sync::{Arc, Mutex};
+
+/// A writer which captures everything written to it
+#[derive(Clone, Default)]
+pub(crate) struct Capture(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Capture {
+    /// Everything written so far
+    pub(crate) fn output(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
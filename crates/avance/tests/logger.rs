@@ -0,0 +1,44 @@
+//! Log records suspend the progress bars of every group, so the logger is tested
+//! in its own process instead of racing with the unit tests.
+#![cfg(feature = "log")]
+
+use std::sync::{Arc, Mutex};
+
+use avance::AvanceLogger;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<String>>>);
+
+impl Log for Capture {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.target() != "ignored"
+    }
+
+    fn log(&self, record: &Record) {
+        self.0.lock().unwrap().push(record.args().to_string());
+    }
+
+    fn flush(&self) {}
+}
+
+#[test]
+fn wrap() {
+    let capture = Capture::default();
+    let logger = AvanceLogger::wrap(capture.clone()).with_max_level(LevelFilter::Info);
+
+    let log = |level, target, msg| {
+        logger.log(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .args(format_args!("{}", msg))
+                .build(),
+        )
+    };
+    log(Level::Info, "avance", "logged");
+    log(Level::Debug, "avance", "too verbose");
+    log(Level::Warn, "ignored", "filtered by the inner logger");
+
+    assert_eq!(*capture.0.lock().unwrap(), ["logged"]);
+}
@@ -0,0 +1,27 @@
+//! The monitor redraws bars of every group, so it's tested in its own process
+//! instead of racing with the unit tests.
+
+use std::thread;
+use std::time::Duration;
+
+use avance::{disable_monitor, enable_monitor, DrawTarget, MultiAvance};
+
+#[path = "../src/testing.rs"]
+mod testing;
+
+use testing::Capture;
+
+#[test]
+fn monitor() {
+    let capture = Capture::default();
+    let multi = MultiAvance::new().with_draw_target(DrawTarget::writer(capture.clone()));
+    // The spinner turns on every tick, so frames drawn by the monitor differ
+    let _pb = multi.bar(100).with_template("{spinner}").unwrap();
+    let len = capture.output().len();
+
+    enable_monitor(Duration::from_millis(10));
+    thread::sleep(Duration::from_millis(250));
+    disable_monitor();
+
+    assert!(capture.output().len() > len);
+}